@@ -1,136 +1,932 @@
-mod instruction;
+mod threaded;
 
-use super::processor::{Environment, Value};
-use self::instruction::{Instruction, InstructionType};
-use std::collections::HashMap;
-use std::cell::RefCell;
+use super::processor::{Environment, RegisterTable};
+use super::processor::instruction::{self, Instruction, InstructionType, ParseError};
+use super::processor::debugger::Debuggable;
+use super::processor::trace::{Record, Tracer};
+use super::processor::watchdog::{Fault, Interrupted, Limits, Watchdog};
+use super::processor::profile::Profile;
+use super::processor::arithmetic::Arithmetic;
+use super::processor::snapshot::Snapshot;
+use super::processor::device::{InputDevice, OutputDevice};
+use super::processor::cycle::CycleDetector;
+use std::cell::{Ref, RefCell};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
 
-struct Program {
+/// One of the programs taking part in a duet
+pub struct Program {
+    id: usize,
     environment: Rc<RefCell<Environment>>,
-    instructions: Rc<Vec<Box<Instruction>>>,
-    instruction_count: HashMap<InstructionType, usize>
+    instructions: Arc<Vec<Box<Instruction>>>,
+    profile: Profile,
+    tracer: Option<Rc<RefCell<Tracer>>>
 }
 
 impl Program {
     fn step(&mut self) -> (InstructionType, Option<i64>) {
-        // Add a scope so that the immutable borrow via env/instruction
-        //  do not prevent us from incrementing the instruction count
-        let (result, instruction_type) = 
-        {
-            let mut env = self.environment.borrow_mut();
-            let pc = env.get_pc();
-            // Make sure we're still in bounds, else die
-            if pc < 0 || pc >= self.instructions.len() as i64 {
-                return (InstructionType::Halt, None);
+        let mut env = self.environment.borrow_mut();
+        let pc = env.get_pc();
+        // Make sure we're still in bounds, else die
+        if pc < 0 || pc >= self.instructions.len() as i64 {
+            return (InstructionType::Halt, None);
+        }
+        // Fetch the instruction at pc
+        let instruction = &self.instructions[pc as usize];
+        // Keep the old register values around to find out what changed
+        let before = self.tracer.as_ref().map(|_| env.exact_registers());
+        // Update the PC
+        env.step_pc();
+        // Execute the instruction at pc
+        let result = instruction.execute(&mut env);
+        if let (Some(tracer), Some(before)) = (self.tracer.as_ref(), before) {
+            if let Some(record) = Record::new(self.id, pc, &**instruction, &before, &env, result) {
+                tracer.borrow_mut().trace(record);
             }
-            // Fetch the instruction at pc
-            let instruction = &self.instructions[pc as usize];
-            // Update the PC
-            env.step_pc();
-            // Execute the instruction at pc
-            let result = instruction.execute(&mut env);
-            (result, instruction.get_type())
-        };
+        }
+        // A faulting instruction is rolled back, so it didn't execute
+        if !env.has_fault() {
+            self.profile.record(pc, instruction.get_type(), result, env.get_pc());
+        }
+        (instruction.get_type(), result)
+    }
+
+    fn instruction_count(&self, instruction_type: InstructionType) -> usize {
+        self.profile.count(instruction_type)
+    }
+
+    /// Checks whether the PC is still inside the program
+    fn in_bounds(&self) -> bool {
+        let pc = self.environment.borrow().get_pc();
+        pc >= 0 && pc < self.instructions.len() as i64
+    }
+
+    fn get_state(&self, status: Status) -> ProgramState {
+        let env = self.environment.borrow();
+        ProgramState {
+            id: self.id,
+            status,
+            pc: env.get_pc(),
+            queue: env.queue(),
+            registers: env.registers(),
+            sent: self.instruction_count(InstructionType::Snd),
+            received: self.instruction_count(InstructionType::Rcv),
+        }
+    }
+}
+
+/// The instructions understood by the duet interpreter
+pub const INSTRUCTION_SET: [InstructionType; 7] = [
+    InstructionType::Snd,
+    InstructionType::Set,
+    InstructionType::Add,
+    InstructionType::Mul,
+    InstructionType::Mod,
+    InstructionType::Rcv,
+    InstructionType::Jgz
+];
 
-        // Log execution if it produced a value
-        if result != None {
-            self.increment_instruction_count(instruction_type.clone());
+/// The instructions understood by the sound card of part 1
+pub const SOUND_INSTRUCTION_SET: [InstructionType; 7] = [
+    InstructionType::Play,
+    InstructionType::Set,
+    InstructionType::Add,
+    InstructionType::Mul,
+    InstructionType::Mod,
+    InstructionType::Recover,
+    InstructionType::Jgz
+];
+
+/// What `snd` and `rcv` mean to the programs of a duet
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// `snd` plays a sound and `rcv` recovers the last sound played if its operand isn't zero.
+    /// Execution stops at the first recovered sound
+    Sound,
+    /// `snd` sends a value to the linked programs and `rcv` waits to receive one
+    Duet,
+}
+
+impl Mode {
+    pub fn instruction_set(&self) -> &'static [InstructionType] {
+        match *self {
+            Mode::Sound => &SOUND_INSTRUCTION_SET,
+            Mode::Duet => &INSTRUCTION_SET,
         }
-        (instruction_type, result)
     }
+}
 
-    fn increment_instruction_count(&mut self, instruction_type: InstructionType) {
-        let entry = self.instruction_count.entry(instruction_type).or_insert(0);
-        *entry = *entry + 1;
+impl Debuggable for Program {
+    fn step(&mut self) -> (InstructionType, Option<i64>) {
+        Program::step(self)
     }
 
-    fn instruction_count(&self, instruction_type: InstructionType) -> usize {
-        *self.instruction_count.get(&instruction_type).unwrap_or(&0)
+    fn get_pc(&self) -> i64 {
+        self.environment.borrow().get_pc()
+    }
+
+    fn registers(&self) -> Vec<(String, i64)> {
+        self.environment.borrow().registers()
+    }
+
+    fn set_register(&mut self, register: &str, value: i64) {
+        self.environment.borrow_mut().set(&register, value);
+    }
+
+    fn disassemble(&self, pc: i64) -> Option<String> {
+        if pc < 0 || pc >= self.instructions.len() as i64 {
+            return None;
+        }
+        let env = self.environment.borrow();
+        Some(instruction::disassemble(&*self.instructions[pc as usize], env.get_register_table()))
+    }
+
+    fn take_fault(&mut self, pc: i64) -> Option<Fault> {
+        self.environment.borrow_mut().take_fault(pc)
+    }
+}
+
+/// How the programs in a duet are linked together.
+/// A link from one program to another means that everything the first program sends
+///  is queued for the second program to receive
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Topology {
+    /// Each program sends to the next, and the last program sends to the first
+    Ring,
+    /// Programs are linked to each other in pairs, i.e. 0 <-> 1, 2 <-> 3, ...
+    /// An odd program out sends nowhere
+    Pairwise,
+    /// Every program sends to every other program
+    Broadcast,
+    /// Program `from` sends to program `to` for every `(from, to)`
+    Edges(Vec<(usize, usize)>),
+}
+
+impl Topology {
+    /// Parses `ring`, `pairwise`, `broadcast` or a comma separated list of edges, e.g. `0->1,1->2`
+    pub fn parse(input: &str) -> Option<Topology> {
+        match input {
+            "ring" => Some(Topology::Ring),
+            "pairwise" => Some(Topology::Pairwise),
+            "broadcast" => Some(Topology::Broadcast),
+            _ => input.split(",").map(|edge| {
+                let parts = edge.trim().split("->").map(|id| id.trim().parse::<usize>().ok()).collect::<Option<Vec<usize>>>()?;
+                if parts.len() != 2 {
+                    return None;
+                }
+                Some((parts[0], parts[1]))
+            }).collect::<Option<Vec<(usize, usize)>>>().map(Topology::Edges)
+        }
+    }
+
+    /// Gets every (from, to) link between `programs` programs
+    /// Returns None if an explicit edge refers to a program that doesn't exist
+    pub fn edges(&self, programs: usize) -> Option<Vec<(usize, usize)>> {
+        match *self {
+            Topology::Ring if programs == 0 => Some(Vec::new()),
+            Topology::Ring => Some((0..programs).map(|i| (i, (i + 1) % programs)).collect()),
+            Topology::Pairwise => Some((0..programs - programs % 2).map(|i| (i, i ^ 1)).collect()),
+            Topology::Broadcast => Some((0..programs).flat_map(|i| (0..programs).filter(move |&j| j != i).map(move |j| (i, j))).collect()),
+            Topology::Edges(ref edges) => {
+                if edges.iter().any(|&(from, to)| from >= programs || to >= programs) {
+                    return None;
+                }
+                Some(edges.clone())
+            }
+        }
+    }
+}
+
+impl Display for Topology {
+    /// Formats the topology the way `parse` reads it
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Topology::Ring => write!(f, "ring"),
+            Topology::Pairwise => write!(f, "pairwise"),
+            Topology::Broadcast => write!(f, "broadcast"),
+            Topology::Edges(ref edges) => {
+                let edges = edges.iter().map(|&(from, to)| format!("{}->{}", from, to)).collect::<Vec<String>>();
+                write!(f, "{}", edges.join(","))
+            }
+        }
+    }
+}
+
+/// Why a duet stopped executing
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Termination {
+    /// Every program is blocked on a `rcv` with nothing left to receive
+    Deadlock,
+    /// Every program ran off the end of its instructions
+    Halted,
+    /// Some programs halted, leaving the rest blocked on a `rcv` that nothing will satisfy
+    Starved,
+    /// A program recovered the frequency of a sound, only possible in `Mode::Sound`
+    Recovered(i64),
+}
+
+impl Termination {
+    /// Works out why a duet stopped from what each program was doing once none could make progress
+    fn of(statuses: &[Status]) -> Termination {
+        if statuses.iter().all(|&status| status == Status::Halted) {
+            Termination::Halted
+        } else if statuses.iter().any(|&status| status == Status::Halted) {
+            Termination::Starved
+        } else {
+            Termination::Deadlock
+        }
+    }
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Termination::Deadlock => write!(f, "deadlock, every program is blocked on rcv"),
+            Termination::Halted => write!(f, "every program halted"),
+            Termination::Starved => write!(f, "starved, the remaining programs are blocked on rcv after others halted"),
+            Termination::Recovered(frequency) => write!(f, "recovered a sound with frequency {}", frequency),
+        }
+    }
+}
+
+/// What a program in a duet was doing when the duet stopped
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    /// The program was still executing, only possible if the duet was interrupted
+    Running,
+    /// The program is waiting on a `rcv` with nothing to receive
+    Blocked,
+    /// The program ran off the end of its instructions
+    Halted,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Status::Running => write!(f, "running"),
+            Status::Blocked => write!(f, "blocked"),
+            Status::Halted => write!(f, "halted"),
+        }
+    }
+}
+
+/// The state of a program when a duet stopped
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProgramState {
+    pub id: usize,
+    pub status: Status,
+    pub pc: i64,
+    /// Values sent to the program which it hasn't received yet
+    pub queue: Vec<i64>,
+    pub registers: Vec<(String, i64)>,
+    pub sent: usize,
+    pub received: usize,
+}
+
+impl Display for ProgramState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let registers = self.registers.iter().map(|&(ref name, value)| format!("{}={}", name, value)).collect::<Vec<String>>();
+        write!(f, "program {}: {} at pc {}, sent {}, received {}, queue {:?}, registers [{}]",
+            self.id, self.status, self.pc, self.sent, self.received, self.queue, registers.join(", "))
+    }
+}
+
+/// The result of executing a duet
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Execution {
+    pub termination: Termination,
+    /// The state of each program, indexed by program
+    pub programs: Vec<ProgramState>,
+}
+
+impl Display for Execution {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "stopped: {}", self.termination)?;
+        for program in self.programs.iter() {
+            write!(f, "\n{}", program)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why `execute_threaded` runs the programs with `execute` instead of on their own threads
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Fallback {
+    /// Devices can't be shared with the threads
+    Devices,
+    /// The program receives from several others, so the order of its queue would depend on how the threads are scheduled
+    SeveralSenders(usize),
+    /// A lone program is checked for cycles, which threads don't do
+    CycleDetection,
+}
+
+impl Display for Fallback {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Fallback::Devices => write!(f, "devices are attached"),
+            Fallback::SeveralSenders(program) => write!(f, "program {} receives from several programs", program),
+            Fallback::CycleDetection => write!(f, "loops are being detected"),
+        }
+    }
+}
+
+/// Why a duet couldn't be created
+#[derive(Debug, PartialEq, Eq)]
+pub enum SetupError<'a> {
+    Parse(ParseError<'a>),
+    /// The topology links programs beyond the number of programs in the duet
+    Topology(Topology, usize),
+}
+
+impl <'a> From<ParseError<'a>> for SetupError<'a> {
+    fn from(error: ParseError<'a>) -> SetupError<'a> {
+        SetupError::Parse(error)
+    }
+}
+
+impl <'a> Display for SetupError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            SetupError::Parse(ref error) => write!(f, "{}", error),
+            SetupError::Topology(ref topology, programs) => write!(f, "Topology {} does not fit {} programs", topology, programs),
+        }
+    }
+}
+
+impl <'a> Error for SetupError<'a> {
+    fn description(&self) -> &str {
+        match *self {
+            SetupError::Parse(ref error) => error.description(),
+            SetupError::Topology(_, _) => "Topology does not fit",
+        }
     }
 }
 
 pub struct Interpreter {
-    program_zero: Program,
-    program_one: Program,
+    programs: Vec<Program>,
+    limits: Limits,
+    detect_cycles: bool,
 }
 
 /// An interpreter that can parse and execute a series of instructions 
 impl Interpreter {
-    pub fn new(input: &str) -> Option<Interpreter> {
-        let instructions = input.split("\n").map(instruction::parse).collect::<Option<Vec<Box<Instruction>>>>();
-        if let Some(instructions) = instructions {
-            // Create a shared, immutable reference to the instructions
-            let shared_instructions = Rc::new(instructions);
-            // Create 2 environments
-            let env_zero = Rc::new(RefCell::new(Environment::new()));
-            let env_one = Rc::new(RefCell::new(Environment::new()));
-            // link the environments
-            env_zero.borrow_mut().link(env_one.clone());
-            env_one.borrow_mut().link(env_zero.clone());
-            // Setup the process id registers
-            env_zero.borrow_mut().set(&"p", 0);
-            env_one.borrow_mut().set(&"p", 1);
-
-            let program_zero = Program { environment: env_zero, instructions: shared_instructions.clone(), instruction_count: HashMap::new() };
-            let program_one = Program { environment: env_one, instructions: shared_instructions, instruction_count: HashMap::new() };
-            return Some(Interpreter {program_zero, program_one });
-        }
-        None
-    }
-
-    /// Executes the interpreter
-    pub fn execute(&mut self) -> usize {
+    /// Creates a duet of 2 programs that send to each other
+    pub fn new(input: &str) -> Result<Interpreter, SetupError> {
+        Interpreter::with_topology(input, 2, Topology::Pairwise)
+    }
+
+    /// Creates `programs` copies of the same program, linked by `topology`.
+    /// Each program's `p` register is set to its index
+    pub fn with_topology(input: &str, programs: usize, topology: Topology) -> Result<Interpreter, SetupError> {
+        Interpreter::with_mode(input, Mode::Duet, programs, topology)
+    }
+
+    /// Creates a single program that plays and recovers sounds
+    pub fn sound(input: &str) -> Result<Interpreter, SetupError> {
+        Interpreter::with_mode(input, Mode::Sound, 1, Topology::Pairwise)
+    }
+
+    /// Creates `programs` copies of the same program, linked by `topology`, with `snd` and `rcv` as given by `mode`.
+    /// Each program's `p` register is set to its index
+    pub fn with_mode(input: &str, mode: Mode, programs: usize, topology: Topology) -> Result<Interpreter, SetupError> {
+        let (instructions, registers) = Interpreter::parse(input, mode)?;
+        let edges = topology.edges(programs).ok_or(SetupError::Topology(topology, programs))?;
+        // Create an environment per program
+        let environments = (0..programs)
+            .map(|_| Rc::new(RefCell::new(Environment::with_registers(registers.clone()))))
+            .collect::<Vec<Rc<RefCell<Environment>>>>();
+        // link the environments
+        for (from, to) in edges {
+            environments[from].borrow_mut().link(environments[to].clone());
+        }
+        for (id, environment) in environments.iter().enumerate() {
+            // Setup the process id register
+            environment.borrow_mut().set(&"p", id as i64);
+        }
+        Ok(Interpreter::from_environments(instructions, environments))
+    }
+
+    /// Creates a duet running `input` from the programs and links saved in `snapshot`.
+    /// Profiles, and so the counts of sent and received values, start from zero
+    pub fn resume<'a>(input: &'a str, mode: Mode, snapshot: &Snapshot) -> Result<Interpreter, ParseError<'a>> {
+        let (instructions, registers) = Interpreter::parse(input, mode)?;
+        Ok(Interpreter::from_environments(instructions, snapshot.restore(&registers)))
+    }
+
+    /// Saves the PC, registers, queue and links of every program so execution can be resumed later
+    pub fn snapshot(&self) -> Snapshot {
+        let environments = self.programs.iter().map(|program| program.environment.borrow()).collect::<Vec<Ref<Environment>>>();
+        Snapshot::capture(&environments.iter().map(|environment| &**environment).collect::<Vec<&Environment>>())
+    }
+
+    fn parse(input: &str, mode: Mode) -> Result<(Vec<Box<Instruction>>, RegisterTable), ParseError> {
+        let mut registers = RegisterTable::new();
+        // The process id register is always used, even if the program never mentions it
+        registers.slot("p");
+        let instructions = instruction::parse_program(input, mode.instruction_set(), &mut registers)?;
+        Ok((instructions, registers))
+    }
+
+    fn from_environments(instructions: Vec<Box<Instruction>>, environments: Vec<Rc<RefCell<Environment>>>) -> Interpreter {
+        // Create a shared, immutable reference to the instructions
+        let shared_instructions = Arc::new(instructions);
+        let programs = environments.into_iter().enumerate().map(|(id, environment)| {
+            let profile = Profile::new(shared_instructions.len());
+            Program { id, environment, instructions: shared_instructions.clone(), profile, tracer: None }
+        }).collect();
+        Interpreter { programs, limits: Limits::none(), detect_cycles: false }
+    }
+
+    /// Records every instruction executed by any program to `tracer`
+    pub fn set_tracer(&mut self, tracer: Rc<RefCell<Tracer>>) {
+        for program in self.programs.iter_mut() {
+            program.tracer = Some(tracer.clone());
+        }
+    }
+
+    /// Stops `execute` with an error once it exceeds `limits`.
+    /// Every attempt to execute an instruction counts as a step, including a `rcv` that blocks
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets what happens when arithmetic overflows in every program.
+    /// `execute` stops with an error when arithmetic fails
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        for program in self.programs.iter() {
+            program.environment.borrow_mut().set_arithmetic(arithmetic);
+        }
+    }
+
+    /// Makes `execute` stop with an error once a lone program comes back to a state it has already been in.
+    /// Several programs, or one reading from an input device, aren't checked as they aren't deterministic
+    ///  on their own
+    pub fn set_cycle_detection(&mut self, enabled: bool) {
+        self.detect_cycles = enabled;
+    }
+
+    /// Reads values for program 0 from `input` whenever nothing has been sent to it
+    pub fn attach_input(&mut self, input: Box<InputDevice>) {
+        if let Some(program) = self.programs.first() {
+            program.environment.borrow_mut().attach_input(input);
+        }
+    }
+
+    /// Writes every value program 0 sends to `output`, as well as to the programs it is linked to
+    pub fn attach_output(&mut self, output: Box<OutputDevice>) {
+        if let Some(program) = self.programs.first() {
+            program.environment.borrow_mut().attach_output(output);
+        }
+    }
+
+    /// Gets every program so they can be driven by a debugger
+    pub fn programs_mut(&mut self) -> Vec<&mut Debuggable> {
+        self.programs.iter_mut().map(|program| program as &mut Debuggable).collect()
+    }
+
+    /// Formats each program's profile as an annotated listing, indexed by program
+    pub fn annotate_profiles(&self) -> Vec<String> {
+        self.programs.iter().map(|program| program.profile.annotate(program)).collect()
+    }
+
+    /// Gets the number of values each program has sent and received, indexed by program
+    pub fn message_counts(&self) -> Vec<(usize, usize)> {
+        self.programs.iter()
+            .map(|program| (program.instruction_count(InstructionType::Snd), program.instruction_count(InstructionType::Rcv)))
+            .collect()
+    }
+
+    /// Executes the interpreter until every program has halted or is blocked, or a sound is recovered.
+    /// Fails with the state of every program if execution exceeds its limits, arithmetic or a device fails
+    ///  or, with cycle detection, a lone program is stuck in a loop
+    pub fn execute(&mut self) -> Result<Execution, Interrupted<Vec<ProgramState>>> {
+        let mut watchdog = Watchdog::new(self.limits);
+        let deterministic = self.programs.len() == 1 && !self.programs[0].environment.borrow().has_input_device();
+        let mut cycles = if self.detect_cycles && deterministic { Some(CycleDetector::new()) } else { None };
         let mut made_progress = true;
-        let mut program_zero_halted = false;
-        let mut program_one_halted = false;
+        let mut statuses = vec![Status::Running; self.programs.len()];
         // While not in deadlock
         while made_progress {
             // Reset progress (might be in dead lock)
             made_progress = false;
-            while !program_zero_halted {
-                let (instruction_type, value) = self.program_zero.step();
-                // If dead, exit
-                if instruction_type == InstructionType::Halt {
-                    program_zero_halted = true;
-                    break;
-                }
-                // If blocking receive, exit
-                if instruction_type == InstructionType::Rcv && value == None {
-                    break;
+            for i in 0..self.programs.len() {
+                let program = &mut self.programs[i];
+                while statuses[i] != Status::Halted {
+                    // If dead, exit
+                    if !program.in_bounds() {
+                        statuses[i] = Status::Halted;
+                        break;
+                    }
+                    if let Err(fault) = watchdog.tick() {
+                        let state = self.programs.iter().zip(statuses.into_iter()).map(|(program, status)| program.get_state(status)).collect();
+                        return Err(Interrupted { fault, state });
+                    }
+                    let cycle = cycles.as_mut().and_then(|cycles| cycles.observe(&program.environment.borrow()));
+                    if let Some(cycle) = cycle {
+                        let state = self.programs.iter().zip(statuses.into_iter()).map(|(program, status)| program.get_state(status)).collect();
+                        return Err(Interrupted { fault: Fault::InfiniteLoop(cycle), state });
+                    }
+                    let pc = program.environment.borrow().get_pc();
+                    let (instruction_type, value) = program.step();
+                    let fault = program.environment.borrow_mut().take_fault(pc);
+                    if let Some(fault) = fault {
+                        // Leave the PC at the instruction that failed
+                        program.environment.borrow_mut().set_pc(pc);
+                        let state = self.programs.iter().zip(statuses.into_iter()).map(|(program, status)| program.get_state(status)).collect();
+                        return Err(Interrupted { fault, state });
+                    }
+                    // If blocking receive, exit
+                    if instruction_type == InstructionType::Rcv && value == None {
+                        statuses[i] = Status::Blocked;
+                        break;
+                    }
+                    // If a sound was recovered, we're done
+                    if let (InstructionType::Recover, Some(frequency)) = (instruction_type, value) {
+                        statuses[i] = Status::Running;
+                        let programs = self.programs.iter().zip(statuses.into_iter()).map(|(program, status)| program.get_state(status)).collect();
+                        return Ok(Execution { termination: Termination::Recovered(frequency), programs });
+                    }
+                    // If we made it here, we did some work.
+                    // Therefore we're not in deadlock (yet)
+                    statuses[i] = Status::Running;
+                    made_progress = true;
                 }
-                // If we made it here, we did some work.
-                // Therefore we're not in deadlock (yet)
-                made_progress = true;
             }
-            while !program_one_halted {
-                let (instruction_type, value) = self.program_one.step();
-                // If dead, exit
-                if instruction_type == InstructionType::Halt {
-                    program_one_halted = true;
-                    break;
-                }
-                // If blocking receive, exit
-                if instruction_type == InstructionType::Rcv && value == None {
-                    break;
-                }
-                // If we made it here, we did some work.
-                // Therefore we're not in deadlock (yet)
-                made_progress = true;
+        }
+        let termination = Termination::of(&statuses);
+        let programs = self.programs.iter().zip(statuses.into_iter()).map(|(program, status)| program.get_state(status)).collect();
+        Ok(Execution { termination, programs })
+    }
+
+    /// Gets why `execute_threaded` would run the programs with `execute`, None if they would run on threads
+    pub fn thread_fallback(&self) -> Option<Fallback> {
+        if self.programs.iter().any(|program| program.environment.borrow().has_devices()) {
+            return Some(Fallback::Devices);
+        }
+        if self.detect_cycles && self.programs.len() == 1 {
+            return Some(Fallback::CycleDetection);
+        }
+        let environments = self.snapshot().environments;
+        let mut senders = vec![HashSet::new(); environments.len()];
+        for (from, environment) in environments.iter().enumerate() {
+            for &to in environment.links.iter() {
+                senders[to].insert(from);
             }
         }
-        self.program_one.instruction_count(InstructionType::Snd)
+        senders.iter().position(|senders| senders.len() > 1).map(Fallback::SeveralSenders)
+    }
+
+    /// Executes the interpreter with every program on its own thread, sending values over channels,
+    ///  until every program has halted or is blocked.
+    /// The result is the same as `execute` as long as every program receives from at most one other,
+    ///  as in a ring or pairwise duet. Otherwise the order values arrive in would depend on how the
+    ///  threads are scheduled, so the programs run with `execute` instead, as they do for any other
+    ///  `thread_fallback`.
+    /// Limits apply to each program separately, waiting on a `rcv` isn't a step, and tracers are not called.
+    /// Fails with the state of every program if execution exceeds its limits or arithmetic fails
+    pub fn execute_threaded(&mut self) -> Result<Execution, Interrupted<Vec<ProgramState>>> {
+        if self.thread_fallback().is_some() {
+            return self.execute();
+        }
+        let environments = self.snapshot().environments;
+        let setups = self.programs.iter().zip(environments.into_iter()).map(|(program, environment)| {
+            let env = program.environment.borrow();
+            threaded::Setup {
+                instructions: program.instructions.clone(),
+                registers: env.get_register_table().clone(),
+                arithmetic: env.get_arithmetic(),
+                environment,
+            }
+        }).collect::<Vec<threaded::Setup>>();
+        let arithmetic = setups.iter().map(|setup| setup.arithmetic).collect::<Vec<Arithmetic>>();
+        let registers = setups.first().map(|setup| setup.registers.clone()).unwrap_or_default();
+        let finished = threaded::run(setups, self.limits);
+
+        // Bring the programs up to date with their threads. Every program was parsed with the same table
+        let snapshot = Snapshot { environments: finished.iter().map(|finished| finished.environment.clone()).collect() };
+        let environments = snapshot.restore(&registers);
+        let mut statuses = Vec::new();
+        let mut fault = None;
+        let mut recovered = None;
+        for (((program, environment), finished), arithmetic) in self.programs.iter_mut().zip(environments.into_iter()).zip(finished.into_iter()).zip(arithmetic.into_iter()) {
+            environment.borrow_mut().set_arithmetic(arithmetic);
+            program.environment = environment;
+            program.profile = finished.profile;
+            statuses.push(finished.status);
+            fault = fault.or(finished.fault);
+            recovered = recovered.or(finished.recovered);
+        }
+        let programs = self.programs.iter().zip(statuses.iter()).map(|(program, &status)| program.get_state(status)).collect();
+        match (fault, recovered) {
+            (Some(fault), _) => Err(Interrupted { fault, state: programs }),
+            (None, Some(frequency)) => Ok(Execution { termination: Termination::Recovered(frequency), programs }),
+            (None, None) => Ok(Execution { termination: Termination::of(&statuses), programs })
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use processor::trace::{self, TraceWriter, TraceChecker};
+    use processor::arithmetic::ArithmeticError;
+    use processor::device::{DeviceError, Reader};
+    use processor::cycle::Cycle;
+
+    /// An output device that keeps what it is sent
+    struct Collect(Rc<RefCell<Vec<i64>>>);
+
+    impl OutputDevice for Collect {
+        fn write(&mut self, value: i64) -> Result<(), DeviceError> {
+            self.0.borrow_mut().push(value);
+            Ok(())
+        }
+    }
 
     #[test]
     fn test_interpreter() {
         let input = "set a 1\nadd a 2\nmul a a\nmod a 5\nsnd a\nset a 0\nrcv a\njgz a -1\nset a 1\njgz a -2";
         let mut interpreter = Interpreter::new(input).unwrap();
-        assert_eq!(interpreter.execute(), 1);
+        assert_eq!(interpreter.execute().unwrap().programs[1].sent, 1);
+    }
+
+    #[test]
+    fn test_sound() {
+        // The example from part 1
+        let input = "set a 1\nadd a 2\nmul a a\nmod a 5\nsnd a\nset a 0\nrcv a\njgz a -1\nset a 1\njgz a -2";
+        let mut interpreter = Interpreter::sound(input).unwrap();
+        let execution = interpreter.execute().unwrap();
+        assert_eq!(execution.termination, Termination::Recovered(4));
+        assert_eq!(execution.programs[0].pc, 7);
+        assert_eq!(Interpreter::sound(input).unwrap().execute_threaded().unwrap(), execution);
+
+        // Without a non-zero rcv nothing is recovered
+        let execution = Interpreter::sound("snd 5\nrcv 0").unwrap().execute().unwrap();
+        assert_eq!(execution.termination, Termination::Halted);
+    }
+
+    #[test]
+    fn test_termination() {
+        // Both programs wait on each other forever
+        let mut interpreter = Interpreter::new("rcv a").unwrap();
+        let execution = interpreter.execute().unwrap();
+        assert_eq!(execution.termination, Termination::Deadlock);
+        assert_eq!(execution.programs[0], ProgramState { id: 0, status: Status::Blocked, pc: 0, queue: vec![], registers: vec![(String::from("p"), 0), (String::from("a"), 0)], sent: 0, received: 0 });
+
+        // Both programs run off the end
+        let mut interpreter = Interpreter::new("snd p\nrcv a").unwrap();
+        assert_eq!(interpreter.execute().unwrap().termination, Termination::Halted);
+
+        // Program one halts straight away, leaving program zero waiting with an unread value
+        let mut interpreter = Interpreter::new("jgz p 3\nsnd 5\nrcv a").unwrap();
+        let execution = interpreter.execute().unwrap();
+        assert_eq!(execution.termination, Termination::Starved);
+        assert_eq!(execution.programs[0].status, Status::Blocked);
+        assert_eq!(execution.programs[0].pc, 2);
+        assert_eq!(execution.programs[1], ProgramState { id: 1, status: Status::Halted, pc: 3, queue: vec![5], registers: vec![(String::from("p"), 1), (String::from("a"), 0)], sent: 0, received: 0 });
+    }
+
+    #[test]
+    fn test_step_limit() {
+        // Both programs keep sending to each other forever
+        let input = "snd 1\nrcv a\njgz a -2";
+        let mut interpreter = Interpreter::new(input).unwrap();
+        interpreter.set_limits(Limits::steps(5));
+        let interrupted = interpreter.execute().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::StepLimit(5));
+        assert_eq!(interrupted.state[0].status, Status::Blocked);
+        assert_eq!(interrupted.state[1].status, Status::Running);
+        assert_eq!(interrupted.state[1].pc, 0);
+    }
+
+    #[test]
+    fn test_resume() {
+        // Every program passes its id along the ring, then passes on what it received
+        let input = "snd p\nrcv a\nadd b a\nsnd a\nrcv a\nadd b a";
+        let mut interpreter = Interpreter::with_topology(input, 3, Topology::Ring).unwrap();
+        interpreter.set_limits(Limits::steps(4));
+        interpreter.execute().unwrap_err();
+        let snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.environments[1].links, vec![2]);
+        assert_eq!(snapshot.environments[2].queue, vec![1]);
+
+        // Carrying on from the snapshot ends up in the same state as an uninterrupted run
+        let mut resumed = Interpreter::resume(input, Mode::Duet, &snapshot).unwrap();
+        assert_eq!(resumed.execute().unwrap().termination, Termination::Halted);
+        let mut expected = Interpreter::with_topology(input, 3, Topology::Ring).unwrap();
+        expected.execute().unwrap();
+        assert_eq!(resumed.snapshot(), expected.snapshot());
+    }
+
+    #[test]
+    fn test_threaded() {
+        let inputs = [
+            // The example from the puzzle, where both programs deadlock
+            "snd 1\nsnd 2\nsnd p\nrcv a\nrcv b\nrcv c\nrcv d",
+            // Both programs halt
+            "snd p\nrcv a",
+            // Program one halts straight away, starving program zero
+            "jgz p 3\nsnd 5\nrcv a",
+            // A long exchange, counting down from 1000 in both programs
+            "set i 1000\nsnd i\nrcv a\nadd b a\nadd i -1\njgz i -4",
+        ];
+        for input in inputs.iter() {
+            let expected = Interpreter::new(input).unwrap().execute().unwrap();
+            let mut interpreter = Interpreter::new(input).unwrap();
+            assert_eq!(interpreter.execute_threaded().unwrap(), expected);
+            assert_eq!(interpreter.message_counts(), expected.programs.iter().map(|program| (program.sent, program.received)).collect::<Vec<(usize, usize)>>());
+        }
+
+        let input = "snd p\nrcv a\nadd b a\nsnd a\nrcv a\nadd b a";
+        let expected = Interpreter::with_topology(input, 5, Topology::Ring).unwrap().execute().unwrap();
+        let mut interpreter = Interpreter::with_topology(input, 5, Topology::Ring).unwrap();
+        assert_eq!(interpreter.execute_threaded().unwrap(), expected);
+
+        // With several senders to a program, the order of its queue depends on the order they ran in
+        let input = "snd p\nsnd p\nrcv a\nrcv b\nrcv c\nrcv d\nmul a 10\nadd a b\nmul a 10\nadd a c\nmul a 10\nadd a d";
+        for &(ref topology, receiver) in [(Topology::Broadcast, 0), (Topology::parse("0->2,1->2,2->0").unwrap(), 2)].iter() {
+            let expected = Interpreter::with_topology(input, 3, topology.clone()).unwrap().execute().unwrap();
+            let mut interpreter = Interpreter::with_topology(input, 3, topology.clone()).unwrap();
+            assert_eq!(interpreter.thread_fallback(), Some(Fallback::SeveralSenders(receiver)));
+            assert_eq!(interpreter.execute_threaded().unwrap(), expected);
+        }
+        assert_eq!(Interpreter::with_topology(input, 5, Topology::Ring).unwrap().thread_fallback(), None);
+    }
+
+    #[test]
+    fn test_threaded_step_limit() {
+        let mut interpreter = Interpreter::new("snd 1\nrcv a\njgz a -2").unwrap();
+        interpreter.set_limits(Limits::steps(100));
+        let interrupted = interpreter.execute_threaded().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::StepLimit(100));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // Program 0 takes the remainder of dividing by zero
+        let input = "set a p\nmod a a";
+        let fault = Fault::Arithmetic { pc: 1, error: ArithmeticError::DivisionByZero };
+        let interrupted = Interpreter::new(input).unwrap().execute().unwrap_err();
+        assert_eq!((interrupted.fault, interrupted.state[0].pc), (fault, 1));
+        let interrupted = Interpreter::new(input).unwrap().execute_threaded().unwrap_err();
+        assert_eq!((interrupted.fault, interrupted.state[0].pc), (fault, 1));
+
+        // Unbounded registers survive running on threads
+        let mut interpreter = Interpreter::new("set a 9223372036854775807\nmul a a").unwrap();
+        interpreter.set_arithmetic(Arithmetic::Unbounded);
+        interpreter.execute_threaded().unwrap();
+        assert_eq!(interpreter.snapshot().environments[1].registers[1].1.to_string(), "85070591730234615847396907784232501249");
+
+        // A value beyond an i64 can't be sent
+        let fault = Fault::Arithmetic { pc: 2, error: ArithmeticError::Overflow };
+        let mut interpreter = Interpreter::new("set a 9223372036854775807\nmul a a\nsnd a").unwrap();
+        interpreter.set_arithmetic(Arithmetic::Unbounded);
+        let interrupted = interpreter.execute().unwrap_err();
+        assert_eq!((interrupted.fault, interrupted.state[0].pc), (fault, 2));
+        // The `snd` that failed isn't counted
+        assert_eq!(interrupted.state[0].sent, 0);
+    }
+
+    #[test]
+    fn test_devices() {
+        // A lone program doubling every value it reads, until the input runs out
+        let input = "rcv a\nmul a 2\nsnd a\njgz 1 -3";
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::with_topology(input, 1, Topology::Pairwise).unwrap();
+        interpreter.attach_input(Box::new(Reader::new("1 2\n-3\n".as_bytes())));
+        interpreter.attach_output(Box::new(Collect(output.clone())));
+        assert_eq!(interpreter.thread_fallback(), Some(Fallback::Devices));
+        let execution = interpreter.execute_threaded().unwrap();
+        assert_eq!(execution.termination, Termination::Deadlock);
+        assert_eq!(*output.borrow(), vec![2, 4, -6]);
+
+        // Input that isn't a number stops the program at the `rcv`
+        let mut interpreter = Interpreter::with_topology(input, 1, Topology::Pairwise).unwrap();
+        interpreter.attach_input(Box::new(Reader::new("1 x".as_bytes())));
+        let interrupted = interpreter.execute().unwrap_err();
+        assert_eq!((interrupted.fault, interrupted.state[0].pc), (Fault::Device { pc: 0, error: DeviceError::NotANumber(1) }, 0));
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        // a counts around 0, 1, 2, 3 forever
+        let input = "set a 3\nadd a 1\nmod a 4\njgz 1 -2";
+        let mut interpreter = Interpreter::with_topology(input, 1, Topology::Pairwise).unwrap();
+        interpreter.set_cycle_detection(true);
+        assert_eq!(interpreter.thread_fallback(), Some(Fallback::CycleDetection));
+        let interrupted = interpreter.execute_threaded().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::InfiniteLoop(Cycle { first: 1, last: 3, period: 12 }));
+        assert_eq!((interrupted.state[0].pc, interrupted.state[0].status), (3, Status::Running));
+
+        // Each program's state depends on the others, so a duet is never checked
+        let mut interpreter = Interpreter::new(input).unwrap();
+        interpreter.set_cycle_detection(true);
+        interpreter.set_limits(Limits::steps(100));
+        assert_eq!(interpreter.execute().unwrap_err().fault, Fault::StepLimit(100));
+    }
+
+    #[test]
+    fn test_profile() {
+        let input = "snd p\nrcv a\njgz a -1";
+        let mut interpreter = Interpreter::new(input).unwrap();
+        interpreter.execute().unwrap();
+        let profiles = interpreter.annotate_profiles();
+        // Program zero receives 1 and spins on the jgz until it blocks on rcv
+        assert!(profiles[0].contains("*       1    1  rcv a\n"));
+        assert!(profiles[0].contains("loops:\n  1..2: 1 iterations, 2 cycles\n"));
+        assert!(profiles[1].contains("loops:\ninstructions:"));
+    }
+
+    #[test]
+    fn test_setup_errors() {
+        assert_eq!(Interpreter::new("snd a\nadd a").err().map(|error| error.to_string()), Some(String::from("line 2: expected 2 operands, found 1 in `add a`")));
+        assert_eq!(Interpreter::with_topology("snd a", 2, Topology::Edges(vec![(0, 2)])).err(), Some(SetupError::Topology(Topology::Edges(vec![(0, 2)]), 2)));
+    }
+
+    #[test]
+    fn test_topology_edges() {
+        assert_eq!(Topology::Ring.edges(3), Some(vec![(0, 1), (1, 2), (2, 0)]));
+        assert_eq!(Topology::Pairwise.edges(3), Some(vec![(0, 1), (1, 0)]));
+        assert_eq!(Topology::Broadcast.edges(3), Some(vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]));
+        assert_eq!(Topology::Edges(vec![(0, 3)]).edges(3), None);
+        assert_eq!(Topology::parse("0->1, 1->2"), Some(Topology::Edges(vec![(0, 1), (1, 2)])));
+        assert_eq!(Topology::parse("0->1->2"), None);
+        assert_eq!(Topology::parse("star"), None);
+        assert_eq!(Topology::Edges(vec![(0, 1), (1, 2)]).to_string(), "0->1,1->2");
+    }
+
+    #[test]
+    fn test_ring() {
+        // Every program passes its id on to the next, then passes on what it received
+        let input = "snd p\nrcv a\nsnd a\nrcv b";
+        let mut interpreter = Interpreter::with_topology(input, 3, Topology::Ring).unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.message_counts(), vec![(2, 2), (2, 2), (2, 2)]);
+        let programs = interpreter.programs_mut();
+        assert_eq!(programs[0].registers(), vec![(String::from("p"), 0), (String::from("a"), 2), (String::from("b"), 1)]);
+        assert_eq!(programs[1].registers(), vec![(String::from("p"), 1), (String::from("a"), 0), (String::from("b"), 2)]);
+        assert_eq!(programs[2].registers(), vec![(String::from("p"), 2), (String::from("a"), 1), (String::from("b"), 0)]);
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let input = "snd p\nrcv a\nrcv b";
+        let mut interpreter = Interpreter::with_topology(input, 3, Topology::Broadcast).unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.message_counts(), vec![(1, 2), (1, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = "snd p\nrcv a";
+        let mut interpreter = Interpreter::new(input).unwrap();
+        let writer = Rc::new(RefCell::new(TraceWriter::new(Vec::new())));
+        interpreter.set_tracer(writer.clone());
+        interpreter.execute().unwrap();
+        let trace = String::from_utf8(writer.borrow().output().clone()).unwrap();
+        assert_eq!(trace, "0\t0\tsnd p\tsnd:0\n1\t0\tsnd p\tsnd:1\n1\t1\trcv a\trcv:0\n0\t1\trcv a\ta=1 rcv:1\n");
+
+        // Replaying the trace should match exactly
+        let mut interpreter = Interpreter::new(input).unwrap();
+        let checker = Rc::new(RefCell::new(TraceChecker::new(trace::parse(&trace).unwrap())));
+        interpreter.set_tracer(checker.clone());
+        interpreter.execute().unwrap();
+        assert_eq!(checker.borrow_mut().finish(), Ok(4));
+    }
+
+    #[test]
+    fn test_trace_sound() {
+        let input = "snd 440\nrcv 0\nrcv 1";
+        let mut interpreter = Interpreter::sound(input).unwrap();
+        let writer = Rc::new(RefCell::new(TraceWriter::new(Vec::new())));
+        interpreter.set_tracer(writer.clone());
+        interpreter.execute().unwrap();
+        let trace = String::from_utf8(writer.borrow().output().clone()).unwrap();
+        assert_eq!(trace, "0\t0\tsnd 440\tsnd:440\n0\t1\trcv 0\t-\n0\t2\trcv 1\trcv:440\n");
+
+        // Playing a different sound doesn't match the trace
+        let mut interpreter = Interpreter::sound("snd 220\nrcv 0\nrcv 1").unwrap();
+        let checker = Rc::new(RefCell::new(TraceChecker::new(trace::parse(&trace).unwrap())));
+        interpreter.set_tracer(checker.clone());
+        interpreter.execute().unwrap();
+        assert_eq!(checker.borrow_mut().finish().unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_debug_programs() {
+        let input = "snd p\nrcv a";
+        let mut interpreter = Interpreter::new(input).unwrap();
+        let mut programs = interpreter.programs_mut();
+        assert_eq!(programs[0].disassemble(1), Some(String::from("rcv a")));
+        // Program zero can't receive until program one has sent
+        assert_eq!(programs[0].step(), (InstructionType::Snd, Some(0)));
+        assert_eq!(programs[0].step(), (InstructionType::Rcv, None));
+        assert_eq!(programs[0].get_pc(), 1);
+        assert_eq!(programs[1].step(), (InstructionType::Snd, Some(1)));
+        assert_eq!(programs[0].step(), (InstructionType::Rcv, Some(1)));
+        assert_eq!(programs[0].registers(), vec![(String::from("p"), 0), (String::from("a"), 1)]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use super::Status;
+use super::super::processor::RegisterTable;
+use super::super::processor::instruction::{Instruction, InstructionType};
+use super::super::processor::snapshot::EnvironmentSnapshot;
+use super::super::processor::watchdog::{Fault, Limits, Watchdog};
+use super::super::processor::profile::Profile;
+use super::super::processor::arithmetic::Arithmetic;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// What each program is doing and how many values are on their way to it
+struct Accounts {
+    statuses: Vec<Status>,
+    pending: Vec<usize>,
+}
+
+impl Accounts {
+    /// Checks whether no program can ever make progress again
+    fn finished(&self) -> bool {
+        self.statuses.iter().zip(self.pending.iter())
+            .all(|(&status, &pending)| status == Status::Halted || (status == Status::Blocked && pending == 0))
+    }
+}
+
+/// Keeps track of the values sent between threads so that a deadlock can be told apart
+///  from a thread that just hasn't been scheduled yet
+struct Mailbox {
+    accounts: Mutex<Accounts>,
+    changed: Condvar,
+    stopped: AtomicBool,
+}
+
+impl Mailbox {
+    fn new(programs: usize) -> Mailbox {
+        let accounts = Accounts { statuses: vec![Status::Running; programs], pending: vec![0; programs] };
+        Mailbox { accounts: Mutex::new(accounts), changed: Condvar::new(), stopped: AtomicBool::new(false) }
+    }
+
+    /// Announces a value for `to`, which must be called before the value is sent on its channel
+    fn post(&self, to: usize) {
+        self.accounts.lock().unwrap().pending[to] += 1;
+        self.changed.notify_all();
+    }
+
+    /// Waits for a value to be sent to program `id`.
+    /// Returns None if every program is halted or blocked, or if execution was stopped
+    fn wait(&self, id: usize, receiver: &Receiver<i64>) -> Option<i64> {
+        let mut accounts = self.accounts.lock().unwrap();
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return None;
+            }
+            if accounts.pending[id] > 0 {
+                accounts.pending[id] -= 1;
+                accounts.statuses[id] = Status::Running;
+                drop(accounts);
+                // The value was posted before it was sent, so it is already on its way
+                return receiver.recv().ok();
+            }
+            accounts.statuses[id] = Status::Blocked;
+            if accounts.finished() {
+                self.changed.notify_all();
+                return None;
+            }
+            accounts = self.changed.wait(accounts).unwrap();
+        }
+    }
+
+    /// Marks program `id` as having run off the end of its instructions
+    fn halt(&self, id: usize) {
+        let mut accounts = self.accounts.lock().unwrap();
+        accounts.statuses[id] = Status::Halted;
+        self.changed.notify_all();
+    }
+
+    /// Makes every thread stop as soon as possible
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        let _accounts = self.accounts.lock().unwrap();
+        self.changed.notify_all();
+    }
+}
+
+/// The state of a program after its thread finished
+pub struct Finished {
+    pub environment: EnvironmentSnapshot,
+    pub profile: Profile,
+    pub status: Status,
+    pub fault: Option<Fault>,
+    /// The frequency of the sound the program recovered, if any
+    pub recovered: Option<i64>,
+}
+
+/// Everything a thread needs to run one program
+pub struct Setup {
+    pub instructions: Arc<Vec<Box<Instruction>>>,
+    /// The table the instructions were parsed with
+    pub registers: RegisterTable,
+    pub arithmetic: Arithmetic,
+    pub environment: EnvironmentSnapshot,
+}
+
+/// One program's thread
+struct Worker {
+    id: usize,
+    environment: EnvironmentSnapshot,
+    receiver: Receiver<i64>,
+    senders: Vec<(usize, Sender<i64>)>,
+    mailbox: Arc<Mailbox>,
+}
+
+impl Worker {
+    /// Runs the program until it halts, blocks forever or exceeds `limits`.
+    /// The receiver is handed back so values that were never received can be collected
+    fn run(self, instructions: Arc<Vec<Box<Instruction>>>, registers: RegisterTable, arithmetic: Arithmetic, limits: Limits) -> (Finished, Receiver<i64>) {
+        let mut environment = self.environment.restore(registers);
+        environment.set_arithmetic(arithmetic);
+        let mut profile = Profile::new(instructions.len());
+        let mut watchdog = Watchdog::new(limits);
+        let mut fault = None;
+        let mut recovered = None;
+        let status = loop {
+            let pc = environment.get_pc();
+            if pc < 0 || pc >= instructions.len() as i64 {
+                self.mailbox.halt(self.id);
+                break Status::Halted;
+            }
+            if self.mailbox.stopped.load(Ordering::SeqCst) {
+                break Status::Running;
+            }
+            if let Err(error) = watchdog.tick() {
+                fault = Some(error);
+                self.mailbox.stop();
+                break Status::Running;
+            }
+            let instruction = &instructions[pc as usize];
+            if instruction.get_type() == InstructionType::Rcv && environment.queue().is_empty() {
+                match self.mailbox.wait(self.id, &self.receiver) {
+                    Some(value) => environment.enqueue(value),
+                    None => break Status::Blocked
+                }
+            }
+            environment.step_pc();
+            let result = instruction.execute(&mut environment);
+            if let Some(error) = environment.take_fault(pc) {
+                // Leave the PC at the instruction that failed
+                environment.set_pc(pc);
+                fault = Some(error);
+                self.mailbox.stop();
+                break Status::Running;
+            }
+            if let (InstructionType::Snd, Some(value)) = (instruction.get_type(), result) {
+                for &(to, ref sender) in self.senders.iter() {
+                    self.mailbox.post(to);
+                    // Every receiver outlives the threads, so sending can't fail
+                    sender.send(value).unwrap();
+                }
+            }
+            profile.record(pc, instruction.get_type(), result, environment.get_pc());
+            if let (InstructionType::Recover, Some(frequency)) = (instruction.get_type(), result) {
+                recovered = Some(frequency);
+                self.mailbox.stop();
+                break Status::Running;
+            }
+        };
+        let environment = EnvironmentSnapshot {
+            pc: environment.get_pc(),
+            registers: environment.exact_registers(),
+            queue: environment.queue(),
+            links: self.environment.links,
+        };
+        (Finished { environment, profile, status, fault, recovered }, self.receiver)
+    }
+}
+
+/// Runs every program in `setups` on its own thread, sending values over channels
+///  along the links between their environments.
+/// Each thread is held to `limits` separately, and waiting on a `rcv` isn't a step.
+/// Returns the state of each program, indexed by program
+pub fn run(setups: Vec<Setup>, limits: Limits) -> Vec<Finished> {
+    let mailbox = Arc::new(Mailbox::new(setups.len()));
+    let (senders, receivers): (Vec<Sender<i64>>, Vec<Receiver<i64>>) = setups.iter().map(|_| mpsc::channel()).unzip();
+    let handles = setups.into_iter().zip(receivers.into_iter()).enumerate().map(|(id, (setup, receiver))| {
+        let Setup { instructions, registers, arithmetic, environment } = setup;
+        let senders = environment.links.iter().map(|&to| (to, senders[to].clone())).collect();
+        let worker = Worker { id, environment, receiver, senders, mailbox: mailbox.clone() };
+        thread::spawn(move || worker.run(instructions, registers, arithmetic, limits))
+    }).collect::<Vec<thread::JoinHandle<(Finished, Receiver<i64>)>>>();
+    let results = handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<(Finished, Receiver<i64>)>>();
+    results.into_iter().map(|(mut finished, receiver)| {
+        // Anything still in the channel was sent but never received
+        finished.environment.queue.extend(receiver.try_iter());
+        finished
+    }).collect()
+}
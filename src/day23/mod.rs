@@ -1,64 +1,337 @@
-mod instruction;
+use super::processor::{Environment, MachineState, RegisterTable};
+use super::processor::instruction::{self, Instruction, InstructionType, ParseError};
+use super::processor::debugger::Debuggable;
+use super::processor::trace::{Record, Tracer};
+use super::processor::watchdog::{Fault, Interrupted, Limits, Watchdog};
+use super::processor::profile::Profile;
+use super::processor::arithmetic::{Arithmetic, ArithmeticError, BigInt};
+use super::processor::optimizer;
+use super::processor::cycle::CycleDetector;
+use super::processor::snapshot::{EnvironmentSnapshot, Snapshot};
+use std::mem;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use super::processor::Environment;
-use self::instruction::{Instruction, InstructionType};
+/// The instructions understood by the coprocessor
+pub const INSTRUCTION_SET: [InstructionType; 4] = [
+    InstructionType::Set,
+    InstructionType::Sub,
+    InstructionType::Mul,
+    InstructionType::Jnz
+];
 
 pub struct Coprocessor {
     environment: Environment,
-    instructions: Vec<Box<Instruction>>
+    instructions: Vec<Box<Instruction>>,
+    tracer: Option<Rc<RefCell<Tracer>>>,
+    limits: Limits,
+    profile: Profile,
+    detect_cycles: bool
 }
 
 /// An interpreter that can parse and execute a series of instructions 
 impl Coprocessor {
-    pub fn new(input: &str) -> Option<Coprocessor> {
-        let instructions = input.split("\n").map(instruction::parse).collect::<Option<Vec<Box<Instruction>>>>();
-        if let Some(instructions) = instructions {
-            return Some(Coprocessor {environment: Environment::new(), instructions});
+    pub fn new(input: &str) -> Result<Coprocessor, ParseError> {
+        let mut registers = RegisterTable::new();
+        let instructions = instruction::parse_program(input, &INSTRUCTION_SET, &mut registers)?;
+        let profile = Profile::new(instructions.len());
+        Ok(Coprocessor {environment: Environment::with_registers(registers), instructions, tracer: None, limits: Limits::none(), profile, detect_cycles: false})
+    }
+
+    /// Carries on from the state saved in `snapshot`. The profile is left as it is
+    pub fn restore(&mut self, snapshot: &EnvironmentSnapshot) {
+        let table = self.environment.get_register_table().clone();
+        let arithmetic = self.environment.get_arithmetic();
+        self.environment = snapshot.restore(table);
+        self.environment.set_arithmetic(arithmetic);
+    }
+
+    /// Saves the PC and registers so execution can be resumed later
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::capture(&[&self.environment])
+    }
+
+    /// Executes the instruction at the current PC
+    pub fn step(&mut self) -> (InstructionType, Option<i64>) {
+        let pc = self.environment.get_pc();
+        // Make sure we're still in bounds, else die
+        if pc < 0 || pc >= self.instructions.len() as i64 {
+            return (InstructionType::Halt, None);
+        }
+        // Get the instruction at pc
+        let instruction = &self.instructions[pc as usize];
+        // Keep the old register values around to find out what changed
+        let before = self.tracer.as_ref().map(|_| self.environment.exact_registers());
+        // Update the PC
+        self.environment.step_pc();
+        // Execute the instruction
+        let result = instruction.execute(&mut self.environment);
+        if let (Some(tracer), Some(before)) = (self.tracer.as_ref(), before) {
+            if let Some(record) = Record::new(0, pc, &**instruction, &before, &self.environment, result) {
+                tracer.borrow_mut().trace(record);
+            }
+        }
+        // A faulting instruction is rolled back, so it didn't execute
+        if !self.environment.has_fault() {
+            self.profile.record(pc, instruction.get_type(), result, self.environment.get_pc());
         }
-        None
+        (instruction.get_type(), result)
     }
 
-    /// Executes the interpreter
-    pub fn execute(&mut self) -> i64 {
-        let mut value = 0;
-        loop {
-            let pc = self.environment.get_pc();
-            // Make sure we're still in bounds, else die
-            if pc < 0 || pc >= self.instructions.len() as i64 {
-                break;
+    /// Records every executed instruction to `tracer`
+    pub fn set_tracer(&mut self, tracer: Rc<RefCell<Tracer>>) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Stops `execute` with an error once it exceeds `limits`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Makes `execute` stop with an error once the coprocessor comes back to a state it has already been in
+    pub fn set_cycle_detection(&mut self, enabled: bool) {
+        self.detect_cycles = enabled;
+    }
+
+    /// Sets what happens when arithmetic overflows.
+    /// `execute` stops with an error when arithmetic fails
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.environment.set_arithmetic(arithmetic);
+    }
+
+    /// Replaces recognised loops with instructions that run them in one step.
+    /// Registers end up the same, but far fewer instructions (including `mul`s) are executed
+    pub fn optimize(&mut self) {
+        let instructions = mem::replace(&mut self.instructions, Vec::new());
+        self.instructions = optimizer::optimize(instructions);
+    }
+
+    /// Sets the value of a register by name
+    pub fn set_register(&mut self, register: &str, value: i64) {
+        self.environment.set(&register, value);
+    }
+
+    /// Sets the exact value of a register by name.
+    /// Fails with an overflow, leaving the register alone, if the value doesn't fit in an i64 without unbounded arithmetic
+    pub fn set_exact_register(&mut self, register: &str, value: BigInt) -> Result<(), ArithmeticError> {
+        if value.to_i64().is_none() && self.environment.get_arithmetic() != Arithmetic::Unbounded {
+            return Err(ArithmeticError::Overflow);
+        }
+        self.environment.set_exact(&register, value);
+        Ok(())
+    }
+
+    /// Gets the name and exact value of every register, in the order the program first uses them
+    pub fn exact_registers(&self) -> Vec<(String, BigInt)> {
+        self.environment.exact_registers()
+    }
+
+    /// Gets the value of a register by name, 0 if the program never uses it
+    pub fn get_register(&self, register: &str) -> i64 {
+        self.environment.get_register_table().find(register).map_or(0, |slot| self.environment.get_register(slot))
+    }
+
+    /// Gets the exact value of a register by name, which may have outgrown an i64 with unbounded arithmetic
+    pub fn get_exact_register(&self, register: &str) -> BigInt {
+        self.environment.get_register_table().find(register).map_or(BigInt::from(0), |slot| self.environment.get_exact_register(slot))
+    }
+
+    /// Gets the execution counts of every instruction executed so far
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Gets the current PC and registers
+    pub fn get_state(&self) -> MachineState {
+        self.environment.get_state()
+    }
+
+    /// Executes the interpreter, returning the number of `mul` instructions executed.
+    /// Fails with the state of the coprocessor if execution exceeds its limits, arithmetic fails (leaving
+    ///  the PC at the instruction that failed) or, with cycle detection, the coprocessor is stuck in a loop
+    pub fn execute(&mut self) -> Result<i64, Interrupted<MachineState>> {
+        let mut watchdog = Watchdog::new(self.limits);
+        let mut cycles = if self.detect_cycles { Some(CycleDetector::new()) } else { None };
+        while self.in_bounds() {
+            if let Err(fault) = watchdog.tick() {
+                return Err(Interrupted { fault, state: self.get_state() });
             }
-            // Get the instruction at pc
-            let instruction = &self.instructions[pc as usize];
-            // If it's a mul, update the value
-            if instruction.get_type() == InstructionType::Mul {
-                value += 1;
+            if let Some(cycle) = cycles.as_mut().and_then(|cycles| cycles.observe(&self.environment)) {
+                return Err(Interrupted { fault: Fault::InfiniteLoop(cycle), state: self.get_state() });
+            }
+            let pc = self.environment.get_pc();
+            self.step();
+            if let Some(fault) = self.environment.take_fault(pc) {
+                self.environment.set_pc(pc);
+                return Err(Interrupted { fault, state: self.get_state() });
             }
-            // Update the PC
-            self.environment.step_pc();
-            // Execute the instruction
-            instruction.execute(&mut self.environment);
-            
         }
-        value
+        Ok(self.profile.count(InstructionType::Mul) as i64)
+    }
+
+    /// Checks whether the PC is still inside the program
+    fn in_bounds(&self) -> bool {
+        let pc = self.environment.get_pc();
+        pc >= 0 && pc < self.instructions.len() as i64
     }
 }
 
-/// Calculates the number of non-prime numbers
-/// bettween [b * 100 + 100_000, b * 100 + 117_000]
-/// This is a translation of the input assembly
-pub fn calculate_non_primes(b: usize) -> usize {
-    let mut current = b * 100 + 100_000;
-    let end = current + 17_000;
-    let mut result = 0;
-    while current <= end {
-        for d in 2..current - 1 {
-            if current % d == 0 {
-                // not prime. 
-                result += 1;
-                break;
-            }
+impl Debuggable for Coprocessor {
+    fn step(&mut self) -> (InstructionType, Option<i64>) {
+        Coprocessor::step(self)
+    }
+
+    fn get_pc(&self) -> i64 {
+        self.environment.get_pc()
+    }
+
+    fn registers(&self) -> Vec<(String, i64)> {
+        self.environment.registers()
+    }
+
+    fn set_register(&mut self, register: &str, value: i64) {
+        self.environment.set(&register, value);
+    }
+
+    fn disassemble(&self, pc: i64) -> Option<String> {
+        if pc < 0 || pc >= self.instructions.len() as i64 {
+            return None;
         }
-        current += 17; 
+        Some(instruction::disassemble(&*self.instructions[pc as usize], self.environment.get_register_table()))
+    }
+
+    fn take_fault(&mut self, pc: i64) -> Option<Fault> {
+        self.environment.take_fault(pc)
     }
-    result
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+    use processor::parse_assignment;
+    use processor::cycle::Cycle;
+    use processor::trace::TraceWriter;
+
+    #[test]
+    fn test_step_limit() {
+        let input = "set a 2\nmul a a\njnz a 0";
+        let mut coprocessor = Coprocessor::new(input).unwrap();
+        coprocessor.set_limits(Limits::steps(10));
+        let interrupted = coprocessor.execute().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::StepLimit(10));
+        assert_eq!(interrupted.state, MachineState { pc: 2, registers: vec![(String::from("a"), 4)] });
+
+        // A program that finishes within its limit is unaffected
+        let mut coprocessor = Coprocessor::new("set a 2\nmul a a").unwrap();
+        coprocessor.set_limits(Limits::steps(2));
+        assert_eq!(coprocessor.execute(), Ok(1));
+    }
+
+    #[test]
+    fn test_resume() {
+        let input = "set b 5\nset a 3\nmul b a\nsub a 1\njnz a -2";
+        let mut expected = Coprocessor::new(input).unwrap();
+        expected.execute().unwrap();
+
+        // Stop part way through the loop, then carry on from a snapshot
+        let mut coprocessor = Coprocessor::new(input).unwrap();
+        coprocessor.set_limits(Limits::steps(6));
+        coprocessor.execute().unwrap_err();
+        let snapshot = Snapshot::try_from(coprocessor.snapshot().to_string().as_str()).unwrap();
+        assert_eq!(snapshot.to_string(), "environment pc=3 registers=b:30,a:2 queue= links=");
+        let mut resumed = Coprocessor::new(input).unwrap();
+        resumed.restore(&snapshot.environments[0]);
+        assert_eq!(resumed.execute(), Ok(1));
+        assert_eq!(resumed.get_state(), expected.get_state());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // 4 * max - max overflows on the way, even though the result of unbounded arithmetic is non-zero
+        let input = "set a 9223372036854775807\nset b a\nmul a 4\nsub a b\njnz a 2\nset c 1";
+        let run = |arithmetic: Arithmetic| {
+            let mut coprocessor = Coprocessor::new(input).unwrap();
+            coprocessor.set_arithmetic(arithmetic);
+            let result = coprocessor.execute().map(|_| (coprocessor.get_exact_register("a").to_string(), coprocessor.get_register("c")));
+            result
+        };
+        assert_eq!(run(Arithmetic::Wrapping), Ok((String::from("9223372036854775805"), 0)));
+        assert_eq!(run(Arithmetic::Saturating), Ok((String::from("0"), 1)));
+        assert_eq!(run(Arithmetic::Unbounded), Ok((String::from("27670116110564327421"), 0)));
+        let interrupted = run(Arithmetic::Checked).unwrap_err();
+        assert_eq!(interrupted.fault, Fault::Arithmetic { pc: 2, error: ArithmeticError::Overflow });
+        assert_eq!(interrupted.fault.to_string(), "arithmetic overflow at pc 2");
+        assert_eq!(interrupted.state.pc, 2);
+
+        // A register beyond an i64 can be a condition, but not a jump offset
+        let mut coprocessor = Coprocessor::new("set a 9223372036854775807\nmul a 2\njnz a 2\nset b 1\njnz 1 a").unwrap();
+        coprocessor.set_arithmetic(Arithmetic::Unbounded);
+        let interrupted = coprocessor.execute().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::Arithmetic { pc: 4, error: ArithmeticError::Overflow });
+        assert_eq!(coprocessor.get_register("b"), 0);
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        // b goes 0, 1, 0 forever
+        let input = "set a 2\nset b 0\nsub b -1\nmul b 0\njnz a -2";
+        let mut coprocessor = Coprocessor::new(input).unwrap();
+        coprocessor.set_cycle_detection(true);
+        let interrupted = coprocessor.execute().unwrap_err();
+        assert_eq!(interrupted.fault, Fault::InfiniteLoop(Cycle { first: 2, last: 4, period: 3 }));
+        assert_eq!(interrupted.fault.to_string(), "infinite loop detected at pc 2..4 with a period of 3 steps");
+        assert_eq!(interrupted.state.pc, 3);
+
+        // A loop that changes registers on every iteration runs to the end
+        let mut coprocessor = Coprocessor::new("set b 5\nsub b 1\njnz b -1").unwrap();
+        coprocessor.set_cycle_detection(true);
+        assert_eq!(coprocessor.execute(), Ok(0));
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = |input, arithmetic| {
+            let mut coprocessor = Coprocessor::new(input).unwrap();
+            coprocessor.set_arithmetic(arithmetic);
+            let writer = Rc::new(RefCell::new(TraceWriter::new(Vec::new())));
+            coprocessor.set_tracer(writer.clone());
+            let _ = coprocessor.execute();
+            let output = writer.borrow().output().clone();
+            String::from_utf8(output).unwrap()
+        };
+        // A register beyond an i64 is traced with its exact value each time it changes
+        let input = "set a 9223372036854775807\nmul a 2\nmul a 2";
+        assert_eq!(trace(input, Arithmetic::Unbounded), "0\t0\tset a 9223372036854775807\ta=9223372036854775807\n\
+            0\t1\tmul a 2\ta=18446744073709551614\n0\t2\tmul a 2\ta=36893488147419103228\n");
+        // An instruction that faults is rolled back, so it isn't traced
+        assert_eq!(trace(input, Arithmetic::Checked), "0\t0\tset a 9223372036854775807\ta=9223372036854775807\n");
+    }
+
+    #[test]
+    fn test_initial_registers() {
+        assert_eq!(parse_assignment("b=84"), Some((String::from("b"), BigInt::from(84))));
+        assert_eq!(parse_assignment(" a = -1 "), Some((String::from("a"), BigInt::from(-1))));
+        assert_eq!(parse_assignment("b"), None);
+        assert_eq!(parse_assignment("1=2"), None);
+        assert_eq!(parse_assignment("pc=2"), None);
+        assert_eq!(parse_assignment("b=x"), None);
+
+        let mut coprocessor = Coprocessor::new("set c b\nmul c 2\nsub a 1").unwrap();
+        assert_eq!(coprocessor.set_exact_register("a", BigInt::from(5)), Ok(()));
+        assert_eq!(coprocessor.set_exact_register("b", BigInt::from(21)), Ok(()));
+        // A register the program doesn't use is reported too
+        assert_eq!(coprocessor.set_exact_register("d", BigInt::from(-3)), Ok(()));
+        // A value beyond an i64 needs unbounded arithmetic
+        assert_eq!(coprocessor.set_exact_register("c", "99999999999999999999".parse().unwrap()), Err(ArithmeticError::Overflow));
+        coprocessor.execute().unwrap();
+        let registers = coprocessor.exact_registers().into_iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<String>>();
+        assert_eq!(registers, vec!["c=42", "b=21", "a=4", "d=-3"]);
+
+        let mut coprocessor = Coprocessor::new("sub a 1").unwrap();
+        coprocessor.set_arithmetic(Arithmetic::Unbounded);
+        assert_eq!(coprocessor.set_exact_register("a", "99999999999999999999".parse().unwrap()), Ok(()));
+        coprocessor.execute().unwrap();
+        assert_eq!(coprocessor.get_exact_register("a").to_string(), "99999999999999999998");
+    }
+}
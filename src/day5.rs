@@ -1,15 +1,36 @@
-pub fn compute_steps_to_exit_maze<F>(maze: &mut [i32], increment: F) -> u32 
+use super::processor::watchdog::{Interrupted, Limits, Watchdog};
+use std::fmt::{self, Display, Formatter};
+
+/// How far through a maze an interrupted walk got
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MazePosition {
+    pub steps: u32,
+    pub index: i32,
+}
+
+impl Display for MazePosition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "at index {} after {} steps", self.index, self.steps)
+    }
+}
+
+/// Walks the maze, failing with the current position if the walk exceeds `limits`
+pub fn compute_steps_to_exit_maze<F>(maze: &mut [i32], increment: F, limits: Limits) -> Result<u32, Interrupted<MazePosition>>
     where F: Fn(i32) -> i32
 {
+    let mut watchdog = Watchdog::new(limits);
     let mut steps = 0;
     let mut i = 0i32;
     while i >= 0 && i < maze.len() as i32 {
+        if let Err(fault) = watchdog.tick() {
+            return Err(Interrupted { fault, state: MazePosition { steps, index: i } });
+        }
         let prev_i = i;
         steps += 1;
         i += maze[i as usize];
         maze[prev_i as usize] += increment(maze[prev_i as usize]);
     }
-    steps
+    Ok(steps)
 }
 
 /// Computes the number of steps needed to exit a maze.
@@ -19,8 +40,8 @@ pub fn compute_steps_to_exit_maze<F>(maze: &mut [i32], increment: F) -> u32
 /// 2) Move to index i + maze[i]
 /// 3) Increment maze[prev_i] by 1
 /// 4) if i is out of maze bounds, done
-pub fn compute_steps_to_exit_simple_maze(maze: &mut [i32]) -> u32 {
-    compute_steps_to_exit_maze(maze, |_| 1)
+pub fn compute_steps_to_exit_simple_maze(maze: &mut [i32], limits: Limits) -> Result<u32, Interrupted<MazePosition>> {
+    compute_steps_to_exit_maze(maze, |_| 1, limits)
 }
 
 /// Computes the number of steps needed to exit a maze.
@@ -30,23 +51,32 @@ pub fn compute_steps_to_exit_simple_maze(maze: &mut [i32]) -> u32 {
 /// 2) Move to index i + maze[i]
 /// 3) Increment maze[prev_i] by 1 if maze[i] < 3, else -1
 /// 4) if i is out of maze bounds, done
-pub fn compute_steps_to_exit_complex_maze(maze: &mut [i32]) -> u32 {
-    compute_steps_to_exit_maze(maze, |maze_value| if maze_value > 2 { -1 } else { 1 })
+pub fn compute_steps_to_exit_complex_maze(maze: &mut [i32], limits: Limits) -> Result<u32, Interrupted<MazePosition>> {
+    compute_steps_to_exit_maze(maze, |maze_value| if maze_value > 2 { -1 } else { 1 }, limits)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use processor::watchdog::Fault;
 
     #[test]
     fn test_simple_maze() {
         let mut maze = vec![0, 3, 0, 1, -3];
-        assert_eq!(compute_steps_to_exit_simple_maze(&mut maze), 5);
+        assert_eq!(compute_steps_to_exit_simple_maze(&mut maze, Limits::none()), Ok(5));
     }
 
     #[test]
     fn test_complex_maze() {
         let mut maze = vec![0, 3, 0, 1, -3];
-        assert_eq!(compute_steps_to_exit_complex_maze(&mut maze), 10);
+        assert_eq!(compute_steps_to_exit_complex_maze(&mut maze, Limits::none()), Ok(10));
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut maze = vec![0, 3, 0, 1, -3];
+        let interrupted = compute_steps_to_exit_simple_maze(&mut maze, Limits::steps(2)).unwrap_err();
+        assert_eq!(interrupted.fault, Fault::StepLimit(2));
+        assert_eq!(interrupted.state, MazePosition { steps: 2, index: 1 });
     }
 }
\ No newline at end of file
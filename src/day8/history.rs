@@ -0,0 +1,90 @@
+/// A value written to a register by the operation at `pc`.
+/// Steps count executed operations from 1, so step 0 is before anything has executed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Assignment {
+    pub step: usize,
+    pub pc: usize,
+    pub register: usize,
+    pub value: i64,
+}
+
+/// Every write to every register, which is enough to recover the value of any register at any step.
+/// Only operations whose condition held are recorded
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct History {
+    /// The writes to each register in step order, indexed by register slot
+    writes: Vec<Vec<Assignment>>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { writes: Vec::new() }
+    }
+
+    /// Records a write, which must come after every write recorded so far
+    pub fn record(&mut self, write: Assignment) {
+        if write.register >= self.writes.len() {
+            self.writes.resize(write.register + 1, Vec::new());
+        }
+        self.writes[write.register].push(write);
+    }
+
+    fn writes(&self, register: usize) -> &[Assignment] {
+        self.writes.get(register).map_or(&[], |writes| writes.as_slice())
+    }
+
+    /// Gets the value of `register` once `step` steps have executed
+    pub fn value_at(&self, register: usize, step: usize) -> i64 {
+        let writes = self.writes(register);
+        // The number of writes made by then
+        let count = match writes.binary_search_by_key(&step, |write| write.step) {
+            Ok(i) => i + 1,
+            Err(i) => i
+        };
+        if count == 0 { 0 } else { writes[count - 1].value }
+    }
+
+    /// Gets the first step at which `register` held its largest value, and the value.
+    /// Registers start at 0, so this is step 0 if the register never went above 0
+    pub fn maximum(&self, register: usize) -> (usize, i64) {
+        self.writes(register).iter().fold((0, 0), |maximum, write| if write.value > maximum.1 { (write.step, write.value) } else { maximum })
+    }
+
+    /// Gets the last write to `register`, None if it was never written
+    pub fn last_write(&self, register: usize) -> Option<Assignment> {
+        self.writes(register).last().cloned()
+    }
+
+    /// Gets the slot of every register that has been written, in slot order
+    pub fn written(&self) -> Vec<usize> {
+        (0..self.writes.len()).filter(|&register| !self.writes[register].is_empty()).collect()
+    }
+
+    /// Gets every write to every register in step order
+    pub fn all(&self) -> Vec<Assignment> {
+        let mut writes = self.writes.iter().flat_map(|writes| writes.iter().cloned()).collect::<Vec<Assignment>>();
+        writes.sort_by_key(|write| write.step);
+        writes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        let mut history = History::new();
+        for &(step, register, value) in [(1, 1, -3), (2, 0, 5), (4, 0, 9), (5, 0, 9), (6, 0, 2)].iter() {
+            history.record(Assignment { step, pc: step - 1, register, value });
+        }
+        let values = (0..8).map(|step| history.value_at(0, step)).collect::<Vec<i64>>();
+        assert_eq!(values, vec![0, 0, 5, 5, 9, 9, 2, 2]);
+        assert_eq!(history.maximum(0), (4, 9));
+        assert_eq!(history.maximum(1), (0, 0));
+        assert_eq!(history.last_write(0).map(|write| write.pc), Some(5));
+        assert_eq!(history.last_write(2), None);
+        assert_eq!(history.written(), vec![0, 1]);
+        assert_eq!(history.all().iter().map(|write| write.step).collect::<Vec<usize>>(), vec![1, 2, 4, 5, 6]);
+    }
+}
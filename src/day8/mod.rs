@@ -0,0 +1,585 @@
+use super::processor::{Environment, RegisterTable, Value};
+use super::processor::arithmetic::Operator;
+use super::processor::watchdog::Fault;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::io::{self, Write};
+
+mod parser;
+mod history;
+
+pub use self::history::{Assignment, History};
+
+/// A condition that can be checked against the environment
+trait Condition {
+    fn is_satisfied(&self, &Environment) -> bool;
+}
+
+/// A mutation that can be made on the environment
+trait Operation {
+    // performs an operation on the environment and returns the slot of the register it modifies,
+    //  along with the value written if the condition held
+    fn perform(&self, &mut Environment) -> (usize, Option<i64>);
+}
+
+/// A representation of an increment of a register in the environment
+/// Increments `register` by `value` if `condition` holds in the current environment
+struct Inc {
+    register: usize,
+    value: Value,
+    condition: Box<Condition>
+}
+
+impl Operation for Inc {
+    fn perform(&self, environment: &mut Environment) -> (usize, Option<i64>) {
+        if !self.condition.is_satisfied(environment) {
+            return (self.register, None);
+        }
+        (self.register, environment.calculate(Operator::Add, self.register, &self.value))
+    }
+}
+
+/// A representation of an decrement of a register in the environment
+/// Decrements `register` by `value` if `condition` holds in the current environment
+struct Dec {
+    register: usize,
+    value: Value,
+    condition: Box<Condition>
+}
+
+impl Operation for Dec {
+    fn perform(&self, environment: &mut Environment) -> (usize, Option<i64>) {
+        if !self.condition.is_satisfied(environment) {
+            return (self.register, None);
+        }
+        (self.register, environment.calculate(Operator::Sub, self.register, &self.value))
+    }
+}
+
+/// A representation of an assignment to a register in the environment
+/// Sets `register` to `value` if `condition` holds in the current environment
+struct Set {
+    register: usize,
+    value: Value,
+    condition: Box<Condition>
+}
+
+impl Operation for Set {
+    fn perform(&self, environment: &mut Environment) -> (usize, Option<i64>) {
+        if !self.condition.is_satisfied(environment) {
+            return (self.register, None);
+        }
+        let value = environment.get_value(&self.value);
+        environment.set_register(self.register, value);
+        (self.register, Some(value))
+    }
+}
+
+/// A representation of a multiplication of a register in the environment
+/// Multiplies `register` by `value` if `condition` holds in the current environment
+struct Mul {
+    register: usize,
+    value: Value,
+    condition: Box<Condition>
+}
+
+impl Operation for Mul {
+    fn perform(&self, environment: &mut Environment) -> (usize, Option<i64>) {
+        if !self.condition.is_satisfied(environment) {
+            return (self.register, None);
+        }
+        (self.register, environment.calculate(Operator::Mul, self.register, &self.value))
+    }
+}
+
+/// A representation of a remainder of a register in the environment
+/// Sets `register` to the remainder of dividing it by `value` if `condition` holds in the current environment
+struct Mod {
+    register: usize,
+    value: Value,
+    condition: Box<Condition>
+}
+
+impl Operation for Mod {
+    fn perform(&self, environment: &mut Environment) -> (usize, Option<i64>) {
+        if !self.condition.is_satisfied(environment) {
+            return (self.register, None);
+        }
+        (self.register, environment.calculate(Operator::Mod, self.register, &self.value))
+    }
+}
+
+/// A representation of an equals condition
+/// returns true if `left` == `right` in the current environment
+struct Eq {
+    left: Value,
+    right: Value,
+}
+
+impl Condition for Eq {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) == environment.get_value(&self.right)
+    } 
+}
+
+/// A representation of an less than condition
+/// returns true if `left` < `right` in the current environment
+struct Lt {
+    left: Value,
+    right: Value,
+}
+
+
+impl Condition for Lt {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) < environment.get_value(&self.right)
+    } 
+}
+
+/// A representation of an greater than condition
+/// returns true if `left` > `right` in the current environment
+struct Gt {
+    left: Value,
+    right: Value,
+}
+
+impl Condition for Gt {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) > environment.get_value(&self.right)
+    } 
+}
+
+/// A representation of an less than or equal condition
+/// returns true if `left` <= `right` in the current environment
+struct Lte {
+    left: Value,
+    right: Value,
+}
+
+impl Condition for Lte {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) <= environment.get_value(&self.right)
+    } 
+}
+
+/// A representation of an greater than or equal condition
+/// returns true if `left` >= `right` in the current environment
+struct Gte {
+    left: Value,
+    right: Value,
+}
+
+impl Condition for Gte {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) >= environment.get_value(&self.right)
+    } 
+}
+
+/// A representation of an not equal condition
+/// returns true if `left` != `right` in the current environment
+struct Ne {
+    left: Value,
+    right: Value,
+}
+
+impl Condition for Ne {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        environment.get_value(&self.left) != environment.get_value(&self.right)
+    } 
+}
+
+/// returns true if both `left` and `right` hold in the current environment
+struct And {
+    left: Box<Condition>,
+    right: Box<Condition>,
+}
+
+impl Condition for And {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        self.left.is_satisfied(environment) && self.right.is_satisfied(environment)
+    }
+}
+
+/// returns true if either `left` or `right` holds in the current environment
+struct Or {
+    left: Box<Condition>,
+    right: Box<Condition>,
+}
+
+impl Condition for Or {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        self.left.is_satisfied(environment) || self.right.is_satisfied(environment)
+    }
+}
+
+/// returns true if `condition` doesn't hold in the current environment
+struct Not {
+    condition: Box<Condition>,
+}
+
+impl Condition for Not {
+    fn is_satisfied(&self, environment: &Environment) -> bool {
+        !self.condition.is_satisfied(environment)
+    }
+}
+
+/// The condition of an operation without an `if`, which always holds
+struct Always;
+
+impl Condition for Always {
+    fn is_satisfied(&self, _environment: &Environment) -> bool {
+        true
+    }
+}
+
+/// Why a line couldn't be parsed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Reason {
+    /// A token other than `expected` was found, None if the line ended early
+    UnexpectedToken { expected: &'static str, found: Option<String> },
+    /// A character that can't start any token
+    UnexpectedCharacter(char),
+    UnknownOperation(String),
+    UnknownComparison(String),
+    /// A value isn't a number that fits in an i64
+    BadLiteral(String),
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Reason::UnexpectedToken { expected, found: Some(ref found) } => write!(f, "expected {}, found `{}`", expected, found),
+            Reason::UnexpectedToken { expected, found: None } => write!(f, "expected {}, found end of line", expected),
+            Reason::UnexpectedCharacter(c) => write!(f, "unexpected character `{}`", c),
+            Reason::UnknownOperation(ref operation) => write!(f, "unknown operation `{}`", operation),
+            Reason::UnknownComparison(ref comparison) => write!(f, "unknown comparison `{}`", comparison),
+            Reason::BadLiteral(ref literal) => write!(f, "`{}` is not a valid number", literal),
+        }
+    }
+}
+
+/// An error on a line of a program. `line` counts from 1 and `text` is the line as written
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError<'a> {
+    pub line: usize,
+    pub text: &'a str,
+    pub reason: Reason,
+}
+
+impl <'a> Display for ParseError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {} in `{}`", self.line, self.reason, self.text)
+    }
+}
+
+impl <'a> Error for ParseError<'a> {
+    fn description(&self) -> &str {
+        match self.reason {
+            Reason::UnexpectedToken { .. } => "Unexpected token",
+            Reason::UnexpectedCharacter(_) => "Unexpected character",
+            Reason::UnknownOperation(_) => "Unknown operation",
+            Reason::UnknownComparison(_) => "Unknown comparison",
+            Reason::BadLiteral(_) => "Bad literal",
+        }
+    }
+}
+
+/// A struct which can interpret operations and apply their results to an environment 
+pub struct Interpreter {
+    operations: Vec<Box<Operation>>,
+    /// The line number and text of each operation
+    lines: Vec<(usize, String)>,
+    environment: Environment,
+    // The largest value seen by the interpreter at any point during execution
+    largest_value: i64,
+    history: History,
+    steps: usize,
+}
+
+/// The operation that last wrote a register
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Writer<'a> {
+    pub step: usize,
+    pub line: usize,
+    pub text: &'a str,
+}
+
+impl Interpreter {
+    /// Parses an input into a series of sequential operations 
+    ///  that are stored inside the Interpreter.
+    /// Each line is `<register> <inc|dec|set|mul|mod> <value> [if <condition>]`, where values are registers or numbers
+    ///  and conditions are comparisons (`==`, `!=`, `<`, `>`, `<=` or `>=`) combined with `and`, `or`, `not` and parentheses.
+    /// Blank lines are skipped
+    pub fn from_str(input: &str) -> Result<Interpreter, ParseError> {
+        let mut registers = RegisterTable::new();
+        let mut operations = Vec::new();
+        let mut lines = Vec::new();
+        for (number, text) in input.split("\n").enumerate() {
+            if text.trim().is_empty() {
+                continue;
+            }
+            let operation = parser::parse(text, &mut registers).map_err(|reason| ParseError { line: number + 1, text: text.trim(), reason })?;
+            operations.push(operation);
+            lines.push((number + 1, String::from(text.trim())));
+        }
+        Ok(Interpreter::with_operations(operations, lines, Environment::with_registers(registers)))
+    }
+
+    fn with_operations(operations: Vec<Box<Operation>>, lines: Vec<(usize, String)>, environment: Environment) -> Interpreter {
+        Interpreter { operations, lines, environment, largest_value: i64::min_value(), history: History::new(), steps: 0 }
+    }
+
+    /// Executes the series of instructions held inside the interpreter.
+    /// Fails if arithmetic fails (e.g. a `mod` by zero), with the index of the failing operation as the PC
+    pub fn execute(&mut self) -> Result<(), Fault> {
+        for (pc, op) in self.operations.iter().enumerate() {
+            let (register, written) = op.perform(&mut self.environment);
+            if let Some(fault) = self.environment.take_fault(pc as i64) {
+                return Err(fault);
+            }
+            self.steps += 1;
+            if let Some(value) = written {
+                self.history.record(Assignment { step: self.steps, pc, register, value });
+            }
+            let value = self.environment.get_register(register);
+            if value > self.largest_value {
+                self.largest_value = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the largest value in the current environment, out of the registers that have been written.
+    /// Registers only read by conditions don't count
+    pub fn get_current_largest_value(&self) -> i64 {
+        self.history.written().into_iter().map(|slot| self.environment.get_register(slot)).max().unwrap_or(i64::min_value())
+    }
+
+    /// Gets the largest value seen at any point during execution
+    pub fn get_largest_value(&self) -> i64 {
+        self.largest_value
+    }
+
+    /// Gets every value written to a register so far
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Gets the value of `register` once `step` operations have executed, step 0 being before any have
+    pub fn value_at(&self, register: &str, step: usize) -> i64 {
+        self.environment.get_register_table().find(register).map_or(0, |slot| self.history.value_at(slot, step))
+    }
+
+    /// Gets the first step at which `register` held its largest value, and the value
+    pub fn maximum(&self, register: &str) -> (usize, i64) {
+        self.environment.get_register_table().find(register).map_or((0, 0), |slot| self.history.maximum(slot))
+    }
+
+    /// Gets the operation that last wrote `register`, None if nothing has
+    pub fn last_writer(&self, register: &str) -> Option<Writer> {
+        let slot = self.environment.get_register_table().find(register)?;
+        self.history.last_write(slot).map(|write| {
+            let (line, ref text) = self.lines[write.pc];
+            Writer { step: write.step, line, text }
+        })
+    }
+
+    /// Writes the history as CSV with a `step,line,register,value` header, one row per write in step order
+    pub fn write_history_csv<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "step,line,register,value")?;
+        let registers = self.environment.get_register_table();
+        for write in self.history.all() {
+            writeln!(output, "{},{},{},{}", write.step, self.lines[write.pc].0, registers.name(write.register).unwrap_or(""), write.value)?;
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn get_value(&mut self, register: &str) -> i64 {
+        self.environment.get(&register)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates an environment with register `a` in slot 0
+    fn environment() -> Environment {
+        let mut registers = RegisterTable::new();
+        registers.slot("a");
+        Environment::with_registers(registers)
+    }
+
+    #[test]
+    fn test_empty_environment_conditions() {
+        let environment = environment();
+
+        // EQ
+        let eq = Eq { left: Value::Register(0), right: Value::Literal(1) };
+        assert!(!eq.is_satisfied(&environment), "a == 1 when env = {} should have failed");
+        let eq = Eq { left: Value::Register(0), right: Value::Literal(0) };
+        assert!(eq.is_satisfied(&environment), "a == 0 when env = {} should have passed");
+
+        // LT
+        let lt = Lt { left: Value::Register(0), right: Value::Literal(-1) };
+        assert!(!lt.is_satisfied(&environment), "a < -1 when env = {} should have failed");
+        let lt = Lt { left: Value::Register(0), right: Value::Literal(1) };
+        assert!(lt.is_satisfied(&environment), "a < 1 when env = {} should have passed");
+
+        // GT
+        let gt = Gt { left: Value::Register(0), right: Value::Literal(1) };
+        assert!(!gt.is_satisfied(&environment), "a > 1 when env = {} should have failed");
+        let gt = Gt { left: Value::Register(0), right: Value::Literal(-1) };
+        assert!(gt.is_satisfied(&environment), "a > -1 when env = {} should have passed");
+
+        // NE
+        let ne = Ne { left: Value::Register(0), right: Value::Literal(0) };
+        assert!(!ne.is_satisfied(&environment), "a != 0 when env = {} should have failed");
+        let ne = Ne { left: Value::Register(0), right: Value::Literal(1) };
+        assert!(ne.is_satisfied(&environment), "a != 1 when env = {} should have passed");
+
+        // LTE
+        let lte = Lte { left: Value::Register(0), right: Value::Literal(-1) };
+        assert!(!lte.is_satisfied(&environment), "a <= -1 when env = {} should have failed");
+        let lte = Lte { left: Value::Register(0), right: Value::Literal(0) };
+        assert!(lte.is_satisfied(&environment), "a <= 0 when env = {} should have passed");
+
+        // GTE
+        let gte = Gte { left: Value::Register(0), right: Value::Literal(1) };
+        assert!(!gte.is_satisfied(&environment), "a >= 1 when env = {} should have failed");
+        let gte = Gte { left: Value::Register(0), right: Value::Literal(0) };
+        assert!(gte.is_satisfied(&environment), "a >= 0 when env = {} should have passed");
+    }
+
+    #[test]
+    fn test_nonempty_environtment_conditions() {
+        let mut environment = environment();
+        environment.set(&"a", 5);
+
+        // EQ
+        let eq = Eq { left: Value::Register(0), right: Value::Literal(5) };
+        assert!(eq.is_satisfied(&environment), "a == 5 when env = {a: 5} should have passed");
+
+        // LT
+        let lt = Lt { left: Value::Register(0), right: Value::Literal(6) };
+        assert!(lt.is_satisfied(&environment), "a < 6 when env = {a: 5} should have passed");
+
+        // Gt
+        let gt = Gt { left: Value::Register(0), right: Value::Literal(4) };
+        assert!(gt.is_satisfied(&environment), "a > 4 when env = {a: 5} should have passed");
+
+        // LTE
+        let lte = Lte { left: Value::Register(0), right: Value::Literal(5) };
+        assert!(lte.is_satisfied(&environment), "a <= 5 when env = {a: 5} should have passed");
+
+        // GTE
+        let gte = Gte { left: Value::Register(0), right: Value::Literal(5) };
+        assert!(gte.is_satisfied(&environment), "a >= 5 when env = {a: 5} should have passed");
+
+        // NE
+        let ne = Ne { left: Value::Register(0), right: Value::Literal(4) };
+        assert!(ne.is_satisfied(&environment), "a != 4 when env = {a: 5} should have passed");
+    }
+
+    #[test]
+    fn test_inc_not_satisfied() {
+        let mut environment = environment();
+        environment.set(&"a", 5);
+
+        let cond = Box::new(Eq { left: Value::Register(0), right: Value::Literal(4) });
+        let op = Box::new(Inc { register: 0, value: Value::Literal(5), condition: cond });
+        let mut interpreter = Interpreter::with_operations(vec![op], vec![(1, String::new())], environment);
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.get_value("a"), 5);
+    }
+
+    #[test]
+    fn test_inc_satisfied() {
+        let mut environment = environment();
+        environment.set(&"a", 5);
+
+        let cond = Box::new(Eq { left: Value::Register(0), right: Value::Literal(5) });
+        let op = Box::new(Inc { register: 0, value: Value::Literal(5), condition: cond });
+        let mut interpreter = Interpreter::with_operations(vec![op], vec![(1, String::new())], environment);
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.get_value("a"), 10);
+    }
+
+    #[test]
+    fn test_dec_not_satisfied() {
+        let mut environment = environment();
+        environment.set(&"a", 5);
+
+        let cond = Box::new(Eq { left: Value::Register(0), right: Value::Literal(4) });
+        let op = Box::new(Dec { register: 0, value: Value::Literal(5), condition: cond });
+        let mut interpreter = Interpreter::with_operations(vec![op], vec![(1, String::new())], environment);
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.get_value("a"), 5);
+    }
+
+    #[test]
+    fn test_dec_satisfied() {
+        let mut environment = environment();
+        environment.set(&"a", 5);
+
+        let cond = Box::new(Eq { left: Value::Register(0), right: Value::Literal(5) });
+        let op = Box::new(Dec { register: 0, value: Value::Literal(5), condition: cond });
+        let mut interpreter = Interpreter::with_operations(vec![op], vec![(1, String::new())], environment);
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.get_value("a"), 0);
+    }
+
+    #[test]
+    fn test_interpreter() {
+        let input = "b inc 5 if a > 1\na inc 1 if b < 5\nc dec -10 if a >= 1\nc inc -20 if c == 10";
+        let mut interpreter = Interpreter::from_str(input).unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!(interpreter.largest_value, 10);
+        assert_eq!(interpreter.get_current_largest_value(), 1);
+
+        // b is only ever compared, so it isn't part of the current values
+        let mut interpreter = Interpreter::from_str("a dec 5 if b == 0").unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!((interpreter.get_current_largest_value(), interpreter.get_largest_value()), (-5, -5));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| Interpreter::from_str(input).err().map(|error| error.to_string());
+        assert_eq!(error("a inc 1 if b > 0\n\nb div 2 if a > 0"), Some(String::from("line 3: unknown operation `div` in `b div 2 if a > 0`")));
+        assert_eq!(error("a inc 1 if b <> 0"), Some(String::from("line 1: unknown comparison `<>` in `a inc 1 if b <> 0`")));
+        assert_eq!(error("a inc 99999999999999999999 if b > 0"), Some(String::from("line 1: `99999999999999999999` is not a valid number in `a inc 99999999999999999999 if b > 0`")));
+        assert_eq!(error("a inc 1 when b > 0"), Some(String::from("line 1: expected `if` or the end of the line, found `when` in `a inc 1 when b > 0`")));
+        // Trailing newlines are fine
+        assert!(Interpreter::from_str("a inc 1 if b > 0\n").is_ok());
+    }
+
+    #[test]
+    fn test_expressions() {
+        let input = "a set 7\nb set a if a > 5 and not (a == 6 or a >= 10)\nc inc b if b == a\nc mul c\nc mod 10 if c != 0\nd dec c if c < b or c > 100";
+        let mut interpreter = Interpreter::from_str(input).unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!((interpreter.get_value("b"), interpreter.get_value("c"), interpreter.get_value("d")), (7, 9, 0));
+        assert_eq!(interpreter.get_largest_value(), 49);
+
+        // A remainder of dividing by zero stops execution
+        let mut interpreter = Interpreter::from_str("a inc 1\nb mod c if a > 0").unwrap();
+        assert_eq!(interpreter.execute().unwrap_err().to_string(), "mod by zero at pc 1");
+    }
+
+    #[test]
+    fn test_history() {
+        let input = "a inc 5\nb inc a if a > 1\n\na dec 7 if b == 2\na set 12\na mod 5";
+        let mut interpreter = Interpreter::from_str(input).unwrap();
+        interpreter.execute().unwrap();
+        assert_eq!((0..6).map(|step| interpreter.value_at("a", step)).collect::<Vec<i64>>(), vec![0, 5, 5, 5, 12, 2]);
+        assert_eq!(interpreter.maximum("a"), (4, 12));
+        assert_eq!(interpreter.last_writer("b"), Some(Writer { step: 2, line: 2, text: "b inc a if a > 1" }));
+        assert_eq!(interpreter.last_writer("nothing"), None);
+
+        let mut csv = Vec::new();
+        interpreter.write_history_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "step,line,register,value\n1,1,a,5\n2,2,b,5\n4,5,a,12\n5,6,a,2\n");
+    }
+}
\ No newline at end of file
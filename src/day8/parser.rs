@@ -0,0 +1,221 @@
+use super::{Condition, Operation, Reason};
+use super::{Inc, Dec, Set, Mul, Mod};
+use super::{Eq, Ne, Lt, Gt, Lte, Gte, And, Or, Not, Always};
+use super::super::processor::{RegisterTable, Value};
+
+/// Words with a meaning of their own, which can't be used as register names
+const KEYWORDS: [&'static str; 4] = ["if", "and", "or", "not"];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Token<'a> {
+    /// A register name, operation or keyword
+    Word(&'a str),
+    Number(&'a str),
+    /// A run of comparison characters, which may not be a valid comparison
+    Comparison(&'a str),
+    Open,
+    Close,
+}
+
+impl <'a> Token<'a> {
+    fn text(&self) -> &'a str {
+        match *self {
+            Token::Word(text) | Token::Number(text) | Token::Comparison(text) => text,
+            Token::Open => "(",
+            Token::Close => ")",
+        }
+    }
+}
+
+/// Gets the length of the prefix of `input` whose characters match `predicate`
+fn span<F>(input: &str, predicate: F) -> usize
+    where F: Fn(char) -> bool {
+    input.find(|c: char| !predicate(c)).unwrap_or(input.len())
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, Reason> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_left();
+    while let Some(c) = rest.chars().next() {
+        let length = match c {
+            '(' | ')' => 1,
+            '<' | '>' | '=' | '!' => span(rest, |c| "<>=!".contains(c)),
+            c if c.is_digit(10) || c == '-' || c == '+' => 1 + span(&rest[1..], |c| c.is_digit(10)),
+            c if c.is_alphabetic() || c == '_' => span(rest, |c| c.is_alphanumeric() || c == '_'),
+            c => return Err(Reason::UnexpectedCharacter(c))
+        };
+        let text = &rest[..length];
+        tokens.push(match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '<' | '>' | '=' | '!' => Token::Comparison(text),
+            c if c.is_alphabetic() || c == '_' => Token::Word(text),
+            _ => Token::Number(text),
+        });
+        rest = rest[length..].trim_left();
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of a single line
+struct Parser<'a, 'r> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    registers: &'r mut RegisterTable,
+}
+
+impl <'a, 'r> Parser<'a, 'r> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn unexpected(&self, expected: &'static str) -> Reason {
+        Reason::UnexpectedToken { expected, found: self.peek().map(|token| String::from(token.text())) }
+    }
+
+    /// Moves past `keyword` if it is next, returning whether it was
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(Token::Word(keyword)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn register(&mut self) -> Result<usize, Reason> {
+        match self.peek() {
+            Some(Token::Word(name)) if !KEYWORDS.contains(&name) => {
+                self.position += 1;
+                Ok(self.registers.slot(name))
+            },
+            _ => Err(self.unexpected("a register"))
+        }
+    }
+
+    /// value := register | number
+    fn value(&mut self) -> Result<Value, Reason> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                self.position += 1;
+                number.parse::<i64>().map(Value::Literal).map_err(|_| Reason::BadLiteral(String::from(number)))
+            },
+            Some(Token::Word(name)) if !KEYWORDS.contains(&name) => self.register().map(Value::Register),
+            _ => Err(self.unexpected("a register or number"))
+        }
+    }
+
+    /// operation := register name value [`if` condition]
+    fn operation(&mut self) -> Result<Box<Operation>, Reason> {
+        let register = self.register()?;
+        let name = match self.peek() {
+            Some(Token::Word(name)) if ["inc", "dec", "set", "mul", "mod"].contains(&name) => name,
+            Some(token) => return Err(Reason::UnknownOperation(String::from(token.text()))),
+            None => return Err(self.unexpected("an operation"))
+        };
+        self.position += 1;
+        let value = self.value()?;
+        let (condition, expected): (Box<Condition>, &'static str) = match self.keyword("if") {
+            true => (self.condition()?, "`and`, `or` or the end of the line"),
+            false => (Box::new(Always), "`if` or the end of the line")
+        };
+        if self.peek().is_some() {
+            return Err(self.unexpected(expected));
+        }
+        Ok(match name {
+            "inc" => Box::new(Inc { register, value, condition }),
+            "dec" => Box::new(Dec { register, value, condition }),
+            "set" => Box::new(Set { register, value, condition }),
+            "mul" => Box::new(Mul { register, value, condition }),
+            _ => Box::new(Mod { register, value, condition }),
+        })
+    }
+
+    /// condition := conjunction (`or` conjunction)*
+    fn condition(&mut self) -> Result<Box<Condition>, Reason> {
+        let mut condition = self.conjunction()?;
+        while self.keyword("or") {
+            let right = self.conjunction()?;
+            condition = Box::new(Or { left: condition, right });
+        }
+        Ok(condition)
+    }
+
+    /// conjunction := negation (`and` negation)*
+    fn conjunction(&mut self) -> Result<Box<Condition>, Reason> {
+        let mut condition = self.negation()?;
+        while self.keyword("and") {
+            let right = self.negation()?;
+            condition = Box::new(And { left: condition, right });
+        }
+        Ok(condition)
+    }
+
+    /// negation := `not` negation | `(` condition `)` | comparison
+    fn negation(&mut self) -> Result<Box<Condition>, Reason> {
+        if self.keyword("not") {
+            return Ok(Box::new(Not { condition: self.negation()? }));
+        }
+        if self.peek() == Some(Token::Open) {
+            self.position += 1;
+            let condition = self.condition()?;
+            if self.peek() != Some(Token::Close) {
+                return Err(self.unexpected("`)`"));
+            }
+            self.position += 1;
+            return Ok(condition);
+        }
+        self.comparison()
+    }
+
+    /// comparison := value (`==` | `!=` | `<` | `>` | `<=` | `>=`) value
+    fn comparison(&mut self) -> Result<Box<Condition>, Reason> {
+        let left = self.value()?;
+        let comparison = match self.peek() {
+            Some(Token::Comparison(comparison)) => comparison,
+            _ => return Err(self.unexpected("a comparison"))
+        };
+        self.position += 1;
+        let right = self.value()?;
+        Ok(match comparison {
+            "==" => Box::new(Eq { left, right }),
+            "!=" => Box::new(Ne { left, right }),
+            "<" => Box::new(Lt { left, right }),
+            ">" => Box::new(Gt { left, right }),
+            "<=" => Box::new(Lte { left, right }),
+            ">=" => Box::new(Gte { left, right }),
+            _ => return Err(Reason::UnknownComparison(String::from(comparison)))
+        })
+    }
+}
+
+/// Parses a single line, allocating the registers it names in `registers`
+pub fn parse(line: &str, registers: &mut RegisterTable) -> Result<Box<Operation>, Reason> {
+    let tokens = tokenize(line)?;
+    Parser { tokens, position: 0, registers }.operation()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("a inc -5 if (b<=c_2)"), Ok(vec![
+            Token::Word("a"), Token::Word("inc"), Token::Number("-5"), Token::Word("if"),
+            Token::Open, Token::Word("b"), Token::Comparison("<="), Token::Word("c_2"), Token::Close
+        ]));
+        assert_eq!(tokenize("a inc 1 if b > 0;"), Err(Reason::UnexpectedCharacter(';')));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line| parse(line, &mut RegisterTable::new()).err();
+        assert_eq!(error("a inc 1 if (b > 0"), Some(Reason::UnexpectedToken { expected: "`)`", found: None }));
+        assert_eq!(error("a inc 1 if b > 0 c"), Some(Reason::UnexpectedToken { expected: "`and`, `or` or the end of the line", found: Some(String::from("c")) }));
+        assert_eq!(error("a inc 1 if and > 0"), Some(Reason::UnexpectedToken { expected: "a register or number", found: Some(String::from("and")) }));
+        assert_eq!(error("a inc 1 if b"), Some(Reason::UnexpectedToken { expected: "a comparison", found: None }));
+        assert_eq!(error("a div 2"), Some(Reason::UnknownOperation(String::from("div"))));
+        assert_eq!(error("a inc 1 if b => 0"), Some(Reason::UnknownComparison(String::from("=>"))));
+        assert_eq!(error("a inc - if b > 0"), Some(Reason::BadLiteral(String::from("-"))));
+    }
+}
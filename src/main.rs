@@ -37,7 +37,11 @@ pub mod day24;
 pub mod day25;
 pub mod util;
 
-use std::io::{self, Read};
+use std::io::{self, Read, Write, BufWriter};
+use std::fs::File;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::Duration;
 use std::convert::TryFrom;
 use std::error::Error;
 use docopt::Docopt;
@@ -51,10 +55,10 @@ Usage:
   advent-2017 spiralmemory [<input>]
   advent-2017 spiralmemory stress [<input>]
   advent-2017 passphrase <variant> [<input>]
-  advent-2017 maze <variant> [<input>]
+  advent-2017 maze [--max-steps=<n>] [--timeout=<seconds>] <variant> [<input>]
   advent-2017 memory redistribute <variant> [<input>]
   advent-2017 tower [<input>]
-  advent-2017 interpret [<input>]
+  advent-2017 interpret [--history=<csv>] [<input>]
   advent-2017 stream [<input>]
   advent-2017 knothash <variant> [<input>]
   advent-2017 hexgrid [<input>]
@@ -64,14 +68,42 @@ Usage:
   advent-2017 generator <a> <b> [<aalignment>] [<balignment>] [<trials>],
   advent-2017 dance <repetitions> [<input>]
   advent-2017 spinlock [<input>]
-  advent-2017 duet [<input>]
+  advent-2017 duet [--programs=<n>] [--topology=<topology>] [--threaded] [--arithmetic=<mode>] [--max-steps=<n>] [--timeout=<seconds>] [--save=<snapshot>] [--resume=<snapshot>] [--rcv-from=<file>] [--snd-to=<file>] [--detect-loops] <variant> [<input>]
   advent-2017 route [<input>]
   advent-2017 particles [<input>]
   advent-2017 enhance <trials> [<input>]
   advent-2017 virus <trials> <variant> [<input>]
-  advent-2017 coprocessor <variant> [<input>]
+  advent-2017 coprocessor [--arithmetic=<mode>] [--max-steps=<n>] [--timeout=<seconds>] [--save=<snapshot>] [--resume=<snapshot>] [--reg=<assignment>]... [--detect-loops] <variant> [<input>]
   advent-2017 bridge <variant> [<input>]
   advent-2017 turing [<input>]
+  advent-2017 debug [--max-steps=<n>] [--timeout=<seconds>] <dialect> <file>
+  advent-2017 trace <dialect> <file> <output>
+  advent-2017 replay <dialect> <file> <trace>
+  advent-2017 profile [--arithmetic=<mode>] [--max-steps=<n>] [--timeout=<seconds>] <dialect> <file>
+  advent-2017 disassemble [--dot] <dialect> <file>
+  advent-2017 decompile <dialect> <file>
+  advent-2017 lint <dialect> <file>
+  advent-2017 repl [--arithmetic=<mode>] [--max-steps=<n>] [--timeout=<seconds>] <dialect>
+
+Options:
+  --programs=<n>          The number of programs in a complex duet, 2 if not given
+  --topology=<topology>   How complex duet programs are linked: ring, pairwise, broadcast or edges, e.g. 0->1,1->0, pairwise if not given
+  --threaded              Run each duet program on its own thread
+  --dot                   Print a Graphviz DOT control flow graph instead of a listing
+  --arithmetic=<mode>     What happens when arithmetic overflows: wrapping, checked, saturating or unbounded [default: wrapping]
+  --max-steps=<n>         Interrupt execution after <n> steps
+  --timeout=<seconds>     Interrupt execution after running for <seconds>
+  --history=<csv>         Write every register write of a day 8 program to <csv>
+  --save=<snapshot>       Save the state of an interrupted execution to <snapshot>
+  --resume=<snapshot>     Carry on from the state saved in <snapshot>, counting instructions from zero
+  --detect-loops          Stop with an error once a lone program comes back to a state it has been in before
+  --reg=<assignment>      Set a register before running, e.g. --reg a=1 --reg b=84
+  --rcv-from=<file>       Program 0 reads integers from <file> when nothing has been sent to it, - for stdin
+  --snd-to=<file>         Write every value program 0 sends to <file>, - for stdout with the result going to stderr
+
+Variants:
+  coprocessor simple      Count the `mul` instructions the program executes
+  coprocessor complex     Optimise the program's loops and run it with a=1, printing h
 ";
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -80,16 +112,68 @@ enum Variant {
     Complex
 }
 
+/// The assembly dialects understood by the processor tools
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+enum Dialect {
+    Duet,
+    Sound,
+    Coprocessor
+}
+
+impl Dialect {
+    fn instruction_set(&self) -> &'static [processor::instruction::InstructionType] {
+        match *self {
+            Dialect::Duet => &day18::INSTRUCTION_SET,
+            Dialect::Sound => &day18::SOUND_INSTRUCTION_SET,
+            Dialect::Coprocessor => &day23::INSTRUCTION_SET,
+        }
+    }
+
+    /// The registers set before a program starts: a duet program's id, and the coprocessor's debug switch
+    fn preset_registers(&self) -> &'static [&'static str] {
+        match *self {
+            Dialect::Duet | Dialect::Sound => &["p"],
+            Dialect::Coprocessor => &["a"],
+        }
+    }
+
+    /// Creates the interpreter for a duet or sound program
+    fn interpreter<'a>(&self, input: &'a str) -> Result<day18::Interpreter, day18::SetupError<'a>> {
+        match *self {
+            Dialect::Sound => day18::Interpreter::sound(input),
+            _ => day18::Interpreter::new(input),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct Args {
     arg_input: Option<String>,
     arg_variant: Option<Variant>,
+    arg_dialect: Option<Dialect>,
+    arg_file: Option<String>,
+    arg_output: Option<String>,
+    arg_trace: Option<String>,
     arg_a: u64,
     arg_b: u64,
     arg_aalignment: Option<u64>,
     arg_balignment: Option<u64>,
     arg_trials: Option<u32>,
     arg_repetitions: u32,
+    flag_programs: Option<usize>,
+    flag_topology: Option<String>,
+    flag_arithmetic: String,
+    flag_max_steps: Option<u64>,
+    flag_timeout: Option<f64>,
+    flag_dot: bool,
+    flag_threaded: bool,
+    flag_history: Option<String>,
+    flag_save: Option<String>,
+    flag_resume: Option<String>,
+    flag_reg: Vec<String>,
+    flag_detect_loops: bool,
+    flag_rcv_from: Option<String>,
+    flag_snd_to: Option<String>,
     cmd_captcha: bool,
     cmd_checksum: bool,
     cmd_spiralmemory: bool,
@@ -116,6 +200,14 @@ struct Args {
     cmd_coprocessor: bool,
     cmd_bridge: bool,
     cmd_turing: bool,
+    cmd_debug: bool,
+    cmd_trace: bool,
+    cmd_replay: bool,
+    cmd_profile: bool,
+    cmd_disassemble: bool,
+    cmd_decompile: bool,
+    cmd_lint: bool,
+    cmd_repl: bool,
 }
 
 impl Args {
@@ -127,8 +219,93 @@ impl Args {
         io::stdin().read_to_string(&mut input).unwrap();
         input
     }
+
+    /// Gets the execution limits set by `--max-steps` and `--timeout`
+    fn get_limits(&self) -> processor::watchdog::Limits {
+        processor::watchdog::Limits {
+            max_steps: self.flag_max_steps,
+            timeout: self.flag_timeout.map(|seconds| Duration::from_millis((seconds * 1000.0) as u64)),
+        }
+    }
+
+    /// Gets the arithmetic mode set by `--arithmetic`, None if it isn't one
+    fn get_arithmetic(&self) -> Option<processor::arithmetic::Arithmetic> {
+        processor::arithmetic::Arithmetic::parse(&self.flag_arithmetic)
+    }
+
+    /// Reads the contents of `<file>`, leaving stdin free for interactive commands
+    fn get_file(&self) -> io::Result<String> {
+        read_file(&self.arg_file.clone().unwrap_or_default())
+    }
+}
+
+fn read_file(path: &str) -> io::Result<String> {
+    let mut input = String::new();
+    File::open(path)?.read_to_string(&mut input)?;
+    Ok(input)
 }
 
+/// Reads a snapshot written by `--save`
+fn read_snapshot(path: &str) -> Result<processor::snapshot::Snapshot, String> {
+    let input = read_file(path).map_err(|error| format!("Could not read snapshot: {}", error))?;
+    let snapshot = processor::snapshot::Snapshot::try_from(input.as_str()).map_err(|error| format!("Could not parse snapshot: {}", error))?;
+    Ok(snapshot)
+}
+
+/// Writes `snapshot` to the file given by `--save`, if any
+fn save_snapshot(path: &Option<String>, snapshot: processor::snapshot::Snapshot) {
+    if let Some(ref path) = *path {
+        match File::create(path).and_then(|mut file| writeln!(file, "{}", snapshot)) {
+            Ok(_) => println!("saved snapshot to {}", path),
+            Err(error) => println!("Could not save snapshot: {}", error)
+        }
+    }
+}
+
+/// Reports that the result of a command couldn't be written.
+/// Nothing is reported for a closed pipe, e.g. into `head`, as nobody is reading any more
+fn report_write_error(error: io::Error) {
+    if error.kind() != io::ErrorKind::BrokenPipe {
+        let _ = writeln!(io::stderr(), "Could not write output: {}", error);
+    }
+}
+
+fn print_interrupted_duet(interrupted: &processor::watchdog::Interrupted<Vec<day18::ProgramState>>) {
+    if let Err(error) = write_interrupted_duet(&mut io::stdout(), interrupted) {
+        report_write_error(error);
+    }
+}
+
+fn write_interrupted_duet(output: &mut Write, interrupted: &processor::watchdog::Interrupted<Vec<day18::ProgramState>>) -> io::Result<()> {
+    writeln!(output, "interrupted: {}", interrupted.fault)?;
+    for program in interrupted.state.iter() {
+        writeln!(output, "{}", program)?;
+    }
+    Ok(())
+}
+
+/// Writes the answer to a duet followed by why it stopped and the state of each program
+fn write_duet(output: &mut Write, mode: day18::Mode, execution: &day18::Execution) -> io::Result<()> {
+    match execution.termination {
+        day18::Termination::Recovered(frequency) => writeln!(output, "{}", frequency)?,
+        _ if mode == day18::Mode::Sound => writeln!(output, "No sound was recovered")?,
+        _ => writeln!(output, "{}", execution.programs.get(1).map_or(0, |program| program.sent))?
+    }
+    writeln!(output, "{}", execution)
+}
+
+/// Attaches the devices given by `--rcv-from` and `--snd-to` to program 0 of a duet
+fn attach_devices(args: &Args, interpreter: &mut day18::Interpreter) -> Result<(), String> {
+    if let Some(ref path) = args.flag_rcv_from {
+        let input = processor::device::open_input(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+        interpreter.attach_input(input);
+    }
+    if let Some(ref path) = args.flag_snd_to {
+        let output = processor::device::open_output(path).map_err(|error| format!("Could not create {}: {}", path, error))?;
+        interpreter.attach_output(output);
+    }
+    Ok(())
+}
 
 fn main() {
     let args: Args = Docopt::new(USAGE).and_then(|d| d.deserialize()).unwrap_or_else(|e| e.exit());
@@ -171,9 +348,14 @@ fn main() {
     } else if args.cmd_maze {
         let input = args.get_input().split("\n").map(|s| s.parse::<i32>().ok()).collect::<Option<Vec<i32>>>();
         if let Some(mut input) = input {
-            match args.arg_variant.unwrap() {
-                Variant::Simple => println!("{}", day5::compute_steps_to_exit_simple_maze(&mut input)),
-                Variant::Complex => println!("{}", day5::compute_steps_to_exit_complex_maze(&mut input)),
+            let limits = args.get_limits();
+            let steps = match args.arg_variant.unwrap() {
+                Variant::Simple => day5::compute_steps_to_exit_simple_maze(&mut input, limits),
+                Variant::Complex => day5::compute_steps_to_exit_complex_maze(&mut input, limits),
+            };
+            match steps {
+                Ok(steps) => println!("{}", steps),
+                Err(interrupted) => println!("{}", interrupted)
             }
             return;
         }
@@ -198,9 +380,21 @@ fn main() {
         println!("Could not parse tower");
     } else if args.cmd_interpret {
         let input = args.get_input();
-        let mut interpreter = day8::Interpreter::from_str(&input).unwrap();
-        interpreter.execute();
-        println!("current largest: {}, largest ever: {}", interpreter.get_current_largest_value(), interpreter.get_largest_value());
+        match day8::Interpreter::from_str(&input) {
+            Ok(mut interpreter) => {
+                if let Err(fault) = interpreter.execute() {
+                    println!("stopped: {}", fault);
+                }
+                println!("current largest: {}, largest ever: {}", interpreter.get_current_largest_value(), interpreter.get_largest_value());
+                if let Some(ref path) = args.flag_history {
+                    match File::create(path).and_then(|mut file| interpreter.write_history_csv(&mut file)) {
+                        Ok(_) => println!("wrote history to {}", path),
+                        Err(error) => println!("Could not write history: {}", error)
+                    }
+                }
+            },
+            Err(error) => println!("{}", error)
+        }
     } else if args.cmd_stream {
         let input = args.get_input();
         let group = day9::Group::from_str(&input);
@@ -272,13 +466,74 @@ fn main() {
             Err(error) => println!("Could not parse input, {}", error)
         }
     } else if args.cmd_duet {
+        if args.flag_rcv_from == Some(String::from("-")) && args.arg_input.is_none() {
+            println!("The program must be given as <input> to receive values from stdin");
+            return;
+        }
         let input = args.get_input();
-        let interpreter = day18::Interpreter::new(&input);
-        if let Some(mut interpreter) = interpreter {
-            println!("{}", interpreter.execute());
+        let arithmetic = match args.get_arithmetic() {
+            Some(arithmetic) => arithmetic,
+            None => { println!("Unknown arithmetic: {}", args.flag_arithmetic); return; }
+        };
+        let mode = match args.arg_variant {
+            Some(Variant::Simple) => day18::Mode::Sound,
+            _ => day18::Mode::Duet
+        };
+        if mode == day18::Mode::Sound && (args.flag_programs.is_some() || args.flag_topology.is_some()) {
+            println!("Only the complex variant runs several programs");
             return;
         }
-        println!("Could not parse input");
+        let interpreter = match (&args.flag_resume, mode) {
+            (&Some(ref path), _) => match read_snapshot(path) {
+                Ok(snapshot) => day18::Interpreter::resume(&input, mode, &snapshot).map_err(day18::SetupError::from),
+                Err(error) => { println!("{}", error); return; }
+            },
+            (&None, day18::Mode::Sound) => day18::Interpreter::sound(&input),
+            (&None, day18::Mode::Duet) => {
+                let topology = args.flag_topology.clone().unwrap_or_else(|| String::from("pairwise"));
+                match day18::Topology::parse(&topology) {
+                    Some(topology) => day18::Interpreter::with_topology(&input, args.flag_programs.unwrap_or(2), topology),
+                    None => { println!("Unknown topology: {}", topology); return; }
+                }
+            }
+        };
+        if mode == day18::Mode::Sound && (args.flag_rcv_from.is_some() || args.flag_snd_to.is_some()) {
+            println!("Only the complex variant sends and receives values");
+            return;
+        }
+        // Keep stdout for the values sent when it is the output device
+        let mut report: Box<Write> = match args.flag_snd_to {
+            Some(ref path) if path == "-" => Box::new(io::stderr()),
+            _ => Box::new(io::stdout())
+        };
+        match interpreter {
+            Ok(mut interpreter) => {
+                interpreter.set_limits(args.get_limits());
+                interpreter.set_arithmetic(arithmetic);
+                interpreter.set_cycle_detection(args.flag_detect_loops);
+                if let Err(error) = attach_devices(&args, &mut interpreter) {
+                    println!("{}", error);
+                    return;
+                }
+                let fallback = if args.flag_threaded { interpreter.thread_fallback() } else { None };
+                let noted = match fallback {
+                    Some(fallback) => writeln!(report, "running without threads as {}", fallback),
+                    None => Ok(())
+                };
+                let result = if args.flag_threaded { interpreter.execute_threaded() } else { interpreter.execute() };
+                let written = match result {
+                    Ok(ref execution) => noted.and_then(|_| write_duet(&mut *report, mode, execution)),
+                    Err(ref interrupted) => noted.and_then(|_| write_interrupted_duet(&mut *report, interrupted))
+                };
+                if let Err(error) = written {
+                    report_write_error(error);
+                }
+                if result.is_err() {
+                    save_snapshot(&args.flag_save, interpreter.snapshot());
+                }
+            },
+            Err(error) => println!("{}", error)
+        }
     } else if args.cmd_route {
         let input = args.get_input();
         let diagram = day19::Diagram::parse(&input);
@@ -321,24 +576,50 @@ fn main() {
         }
     } else if args.cmd_coprocessor {
         let input = args.get_input();
-        match args.arg_variant.unwrap_or(Variant::Simple) {
-            Variant::Simple => {
-                let mut coprocessor = day23::Coprocessor::new(&input);
-                if let Some(ref mut coprocessor) = coprocessor {
-                    println!("{}", coprocessor.execute());
-                    return;
-                }
-                println!("Could not parse instructions");
-            },
-            Variant::Complex => {
-                let parsed = input.parse::<usize>();
-                if let Ok(parsed) = parsed {
-                    println!("{}", day23::calculate_non_primes(parsed));
-                    return;
-                }
-                println!("Input must be a positive integer");
+        let limits = args.get_limits();
+        let arithmetic = match args.get_arithmetic() {
+            Some(arithmetic) => arithmetic,
+            None => { println!("Unknown arithmetic: {}", args.flag_arithmetic); return; }
+        };
+        let mut coprocessor = match day23::Coprocessor::new(&input) {
+            Ok(coprocessor) => coprocessor,
+            Err(error) => { println!("{}", error); return; }
+        };
+        if let Some(ref path) = args.flag_resume {
+            match read_snapshot(path) {
+                Ok(ref snapshot) if snapshot.environments.len() == 1 => coprocessor.restore(&snapshot.environments[0]),
+                Ok(_) => { println!("The snapshot must be of a single program"); return; },
+                Err(error) => { println!("{}", error); return; }
             }
-        }   
+        }
+        coprocessor.set_limits(limits);
+        coprocessor.set_arithmetic(arithmetic);
+        coprocessor.set_cycle_detection(args.flag_detect_loops);
+        let variant = args.arg_variant.unwrap_or(Variant::Simple);
+        if variant == Variant::Complex {
+            coprocessor.optimize();
+            // A resumed program already has its debug switch set
+            if args.flag_resume.is_none() {
+                coprocessor.set_register("a", 1);
+            }
+        }
+        for assignment in args.flag_reg.iter() {
+            match processor::parse_assignment(assignment).map(|(name, value)| coprocessor.set_exact_register(&name, value)) {
+                Some(Ok(())) => {},
+                Some(Err(_)) => { println!("{} doesn't fit in a register without --arithmetic=unbounded", assignment); return; },
+                None => { println!("Bad register assignment: {}, expected <register>=<value>", assignment); return; }
+            }
+        }
+        let result = coprocessor.execute();
+        let registers = coprocessor.exact_registers().iter().map(|&(ref name, ref value)| format!("{}={}", name, value)).collect::<Vec<String>>();
+        match result {
+            Ok(muls) if variant == Variant::Simple => println!("{}\nregisters [{}]", muls, registers.join(", ")),
+            Ok(_) => println!("{}\nregisters [{}]", coprocessor.get_exact_register("h"), registers.join(", ")),
+            Err(interrupted) => {
+                println!("{}", interrupted);
+                save_snapshot(&args.flag_save, coprocessor.snapshot());
+            }
+        }
     } else if args.cmd_bridge {
         let input = args.get_input();
         let bridge = match args.arg_variant.unwrap_or(Variant::Simple) {
@@ -356,5 +637,196 @@ fn main() {
             Ok(ref mut turing_machine) => println!("{}", turing_machine.run()),
             Err(error) => println!("{}", error)
         }
+    } else if args.cmd_debug {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        let dialect = args.arg_dialect.unwrap();
+        let result = match dialect {
+            Dialect::Duet | Dialect::Sound => match dialect.interpreter(&input) {
+                Ok(mut interpreter) => processor::debugger::run_session(interpreter.programs_mut(), args.get_limits(), stdin.lock(), &mut stdout.lock()),
+                Err(error) => { println!("{}", error); return; }
+            },
+            Dialect::Coprocessor => match day23::Coprocessor::new(&input) {
+                Ok(mut coprocessor) => processor::debugger::run_session(vec![&mut coprocessor], args.get_limits(), stdin.lock(), &mut stdout.lock()),
+                Err(error) => { println!("{}", error); return; }
+            }
+        };
+        if let Err(error) = result {
+            println!("{}", error);
+        }
+    } else if args.cmd_trace {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let output = match File::create(args.arg_output.clone().unwrap_or_default()) {
+            Ok(output) => output,
+            Err(error) => { println!("Could not create trace: {}", error); return; }
+        };
+        let writer = Rc::new(RefCell::new(processor::trace::TraceWriter::new(BufWriter::new(output))));
+        let dialect = args.arg_dialect.unwrap();
+        match dialect {
+            Dialect::Duet | Dialect::Sound => match dialect.interpreter(&input) {
+                Ok(mut interpreter) => {
+                    interpreter.set_tracer(writer.clone());
+                    match interpreter.execute() {
+                        Ok(execution) => println!("{}", execution),
+                        Err(interrupted) => print_interrupted_duet(&interrupted)
+                    }
+                },
+                Err(error) => { println!("{}", error); return; }
+            },
+            Dialect::Coprocessor => match day23::Coprocessor::new(&input) {
+                Ok(mut coprocessor) => {
+                    coprocessor.set_tracer(writer.clone());
+                    match coprocessor.execute() {
+                        Ok(muls) => println!("{}", muls),
+                        Err(interrupted) => println!("{}", interrupted)
+                    }
+                },
+                Err(error) => { println!("{}", error); return; }
+            }
+        };
+        let result = writer.borrow_mut().finish();
+        if let Err(error) = result {
+            println!("Could not write trace: {}", error);
+        }
+    } else if args.cmd_replay {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let trace = match read_file(&args.arg_trace.clone().unwrap_or_default()) {
+            Ok(trace) => trace,
+            Err(error) => { println!("Could not read trace: {}", error); return; }
+        };
+        let records = match processor::trace::parse(&trace) {
+            Ok(records) => records,
+            Err(error) => { println!("{}", error); return; }
+        };
+        let checker = Rc::new(RefCell::new(processor::trace::TraceChecker::new(records)));
+        let dialect = args.arg_dialect.unwrap();
+        match dialect {
+            Dialect::Duet | Dialect::Sound => match dialect.interpreter(&input) {
+                Ok(mut interpreter) => {
+                    interpreter.set_tracer(checker.clone());
+                    let _ = interpreter.execute();
+                },
+                Err(error) => { println!("{}", error); return; }
+            },
+            Dialect::Coprocessor => match day23::Coprocessor::new(&input) {
+                Ok(mut coprocessor) => {
+                    coprocessor.set_tracer(checker.clone());
+                    let _ = coprocessor.execute();
+                },
+                Err(error) => { println!("{}", error); return; }
+            }
+        };
+        let result = checker.borrow_mut().finish();
+        match result {
+            Ok(records) => println!("Trace matches ({} records)", records),
+            Err(mismatch) => println!("{}", mismatch)
+        }
+    } else if args.cmd_profile {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let limits = args.get_limits();
+        let arithmetic = match args.get_arithmetic() {
+            Some(arithmetic) => arithmetic,
+            None => { println!("Unknown arithmetic: {}", args.flag_arithmetic); return; }
+        };
+        let dialect = args.arg_dialect.unwrap();
+        match dialect {
+            Dialect::Duet | Dialect::Sound => match dialect.interpreter(&input) {
+                Ok(mut interpreter) => {
+                    interpreter.set_limits(limits);
+                    interpreter.set_arithmetic(arithmetic);
+                    if let Err(interrupted) = interpreter.execute() {
+                        print_interrupted_duet(&interrupted);
+                    }
+                    for (id, profile) in interpreter.annotate_profiles().iter().enumerate() {
+                        println!("program {}:\n{}", id, profile);
+                    }
+                },
+                Err(error) => println!("{}", error)
+            },
+            Dialect::Coprocessor => match day23::Coprocessor::new(&input) {
+                Ok(mut coprocessor) => {
+                    coprocessor.set_limits(limits);
+                    coprocessor.set_arithmetic(arithmetic);
+                    if let Err(interrupted) = coprocessor.execute() {
+                        println!("{}", interrupted);
+                    }
+                    println!("{}", coprocessor.profile().annotate(&coprocessor));
+                },
+                Err(error) => println!("{}", error)
+            }
+        }
+    } else if args.cmd_disassemble {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let mut registers = processor::RegisterTable::new();
+        let instructions = processor::instruction::parse_program(&input, args.arg_dialect.unwrap().instruction_set(), &mut registers);
+        match instructions {
+            Ok(instructions) => {
+                let graph = processor::cfg::ControlFlowGraph::new(&instructions);
+                if args.flag_dot {
+                    println!("{}", graph.to_dot(&instructions, &registers));
+                } else {
+                    println!("{}", graph.listing(&instructions, &registers));
+                }
+            },
+            Err(error) => println!("{}", error)
+        }
+    } else if args.cmd_decompile {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let mut registers = processor::RegisterTable::new();
+        let instructions = processor::instruction::parse_program(&input, args.arg_dialect.unwrap().instruction_set(), &mut registers);
+        match instructions {
+            Ok(instructions) => println!("{}", processor::decompiler::decompile(&instructions, &registers)),
+            Err(error) => println!("{}", error)
+        }
+    } else if args.cmd_lint {
+        let input = match args.get_file() {
+            Ok(input) => input,
+            Err(error) => { println!("Could not read program: {}", error); return; }
+        };
+        let dialect = args.arg_dialect.unwrap();
+        let mut registers = processor::RegisterTable::new();
+        let instructions = processor::instruction::parse_program(&input, dialect.instruction_set(), &mut registers);
+        match instructions {
+            Ok(instructions) => {
+                let warnings = processor::lint::lint(&instructions, &registers, dialect.preset_registers());
+                for warning in warnings.iter() {
+                    println!("warning: {}", warning);
+                }
+                println!("{} warnings", warnings.len());
+            },
+            Err(error) => println!("{}", error)
+        }
+    } else if args.cmd_repl {
+        let arithmetic = match args.get_arithmetic() {
+            Some(arithmetic) => arithmetic,
+            None => { println!("Unknown arithmetic: {}", args.flag_arithmetic); return; }
+        };
+        let mut repl = processor::repl::Repl::new(args.arg_dialect.unwrap().instruction_set());
+        repl.set_limits(args.get_limits());
+        repl.set_arithmetic(arithmetic);
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        if let Err(error) = repl.run_session(stdin.lock(), &mut stdout.lock()) {
+            println!("{}", error);
+        }
     }
 }
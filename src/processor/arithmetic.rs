@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// What happens when arithmetic on a register overflows an i64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Results wrap around, as in two's complement hardware
+    Wrapping,
+    /// An overflow is an error, stopping the program at the instruction that overflowed
+    Checked,
+    /// Results are clamped to the smallest or largest i64
+    Saturating,
+    /// Registers grow as large as they need to
+    Unbounded,
+}
+
+impl Default for Arithmetic {
+    fn default() -> Arithmetic {
+        Arithmetic::Wrapping
+    }
+}
+
+impl Arithmetic {
+    /// Parses `wrapping`, `checked`, `saturating` or `unbounded`
+    pub fn parse(input: &str) -> Option<Arithmetic> {
+        match input {
+            "wrapping" => Some(Arithmetic::Wrapping),
+            "checked" => Some(Arithmetic::Checked),
+            "saturating" => Some(Arithmetic::Saturating),
+            "unbounded" => Some(Arithmetic::Unbounded),
+            _ => None
+        }
+    }
+
+    /// Applies `operator` to two i64s.
+    /// Unbounded arithmetic fails with an overflow when the result needs to be promoted to a `BigInt`
+    pub fn apply(&self, operator: Operator, a: i64, b: i64) -> Result<i64, ArithmeticError> {
+        if operator == Operator::Mod {
+            // The remainder always fits, even for i64::MIN % -1
+            return if b == 0 { Err(ArithmeticError::DivisionByZero) } else { Ok(a.wrapping_rem(b)) };
+        }
+        match *self {
+            Arithmetic::Wrapping => Ok(match operator {
+                Operator::Add => a.wrapping_add(b),
+                Operator::Sub => a.wrapping_sub(b),
+                _ => a.wrapping_mul(b),
+            }),
+            Arithmetic::Saturating => Ok(match operator {
+                Operator::Add => a.saturating_add(b),
+                Operator::Sub => a.saturating_sub(b),
+                _ => a.saturating_mul(b),
+            }),
+            Arithmetic::Checked | Arithmetic::Unbounded => match operator {
+                Operator::Add => a.checked_add(b),
+                Operator::Sub => a.checked_sub(b),
+                _ => a.checked_mul(b),
+            }.ok_or(ArithmeticError::Overflow)
+        }
+    }
+}
+
+/// An arithmetic instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Mod,
+}
+
+/// Why an arithmetic instruction couldn't produce a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    Overflow,
+    DivisionByZero,
+}
+
+impl Display for ArithmeticError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ArithmeticError::Overflow => write!(f, "arithmetic overflow"),
+            ArithmeticError::DivisionByZero => write!(f, "mod by zero"),
+        }
+    }
+}
+
+/// An arbitrary precision integer, used for registers that outgrow an i64
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    negative: bool,
+    /// Base 2^32 digits, least significant first, without trailing zeros
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        BigInt { negative, magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    /// Gets the value as an i64 if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let magnitude = self.magnitude.iter().rev().fold(0u64, |value, &digit| value << 32 | digit as u64);
+        match self.negative {
+            true if magnitude <= 1 << 63 => Some((magnitude as i64).wrapping_neg()),
+            false if magnitude < 1 << 63 => Some(magnitude as i64),
+            _ => None
+        }
+    }
+
+    /// Gets the value as an i64, clamped to the smallest or largest i64 if it doesn't fit.
+    /// This keeps the sign, and whether the value is zero
+    pub fn saturate(&self) -> i64 {
+        self.to_i64().unwrap_or(if self.negative { i64::min_value() } else { i64::max_value() })
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.magnitude, &other.magnitude));
+        }
+        match compare_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.magnitude, &self.magnitude)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.magnitude, &other.magnitude)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&BigInt::new(!other.negative, other.magnitude.clone()))
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.magnitude.iter().enumerate() {
+                let digit = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = digit as u32;
+                carry = digit >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+
+    /// Gets the remainder of dividing by `other`, which has the sign of `self` like i64's `%`.
+    /// Returns None if `other` is zero
+    pub fn rem(&self, other: &BigInt) -> Option<BigInt> {
+        if other.is_zero() {
+            return None;
+        }
+        // Long division, one bit at a time
+        let mut remainder = Vec::new();
+        for bit in (0..self.magnitude.len() * 32).rev() {
+            remainder = shift_left(&remainder);
+            if self.magnitude[bit / 32] >> (bit % 32) & 1 == 1 {
+                remainder = add_magnitudes(&remainder, &[1]);
+            }
+            if compare_magnitudes(&remainder, &other.magnitude) != Ordering::Less {
+                remainder = sub_magnitudes(&remainder, &other.magnitude);
+            }
+        }
+        Some(BigInt::new(self.negative, remainder))
+    }
+
+    /// Applies an arithmetic operator, returning None when dividing by zero
+    pub fn apply(&self, operator: Operator, other: &BigInt) -> Option<BigInt> {
+        match operator {
+            Operator::Add => Some(self.add(other)),
+            Operator::Sub => Some(self.sub(other)),
+            Operator::Mul => Some(self.mul(other)),
+            Operator::Mod => self.rem(other),
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let magnitude = (value as i128).abs() as u64;
+        BigInt::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::new();
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let digit = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(digit as u32);
+        carry = digit >> 32;
+    }
+    if carry != 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+/// Subtracts `b` from `a`, which must be at least as large
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::new();
+    let mut borrow = 0i64;
+    for i in 0..a.len() {
+        let mut digit = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if digit < 0 { 1 } else { 0 };
+        digit += borrow << 32;
+        difference.push(digit as u32);
+    }
+    while difference.last() == Some(&0) {
+        difference.pop();
+    }
+    difference
+}
+
+fn shift_left(a: &[u32]) -> Vec<u32> {
+    let mut shifted = Vec::new();
+    let mut carry = 0;
+    for &digit in a.iter() {
+        shifted.push(digit << 1 | carry);
+        carry = digit >> 31;
+    }
+    if carry != 0 {
+        shifted.push(carry);
+    }
+    shifted
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Peel off 9 decimal digits at a time
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let mut remainder = 0u64;
+            for digit in magnitude.iter_mut().rev() {
+                let value = remainder << 32 | *digit as u64;
+                *digit = (value / 1_000_000_000) as u32;
+                remainder = value % 1_000_000_000;
+            }
+            while magnitude.last() == Some(&0) {
+                magnitude.pop();
+            }
+            chunks.push(remainder);
+        }
+        let mut output = if self.negative { String::from("-") } else { String::new() };
+        output.push_str(&chunks.pop().unwrap_or(0).to_string());
+        for chunk in chunks.iter().rev() {
+            output.push_str(&format!("{:09}", chunk));
+        }
+        write!(f, "{}", output)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ();
+    fn from_str(input: &str) -> Result<BigInt, ()> {
+        let (negative, digits) = match input.starts_with("-") {
+            true => (true, &input[1..]),
+            false => (false, input),
+        };
+        if digits.is_empty() {
+            return Err(());
+        }
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            let digit = c.to_digit(10).ok_or(())?;
+            let mut carry = digit as u64;
+            for existing in magnitude.iter_mut() {
+                let value = *existing as u64 * 10 + carry;
+                *existing = value as u32;
+                carry = value >> 32;
+            }
+            if carry != 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+        Ok(BigInt::new(negative, magnitude))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_modes() {
+        let max = i64::max_value();
+        assert_eq!(Arithmetic::Wrapping.apply(Operator::Add, max, 1), Ok(i64::min_value()));
+        assert_eq!(Arithmetic::Saturating.apply(Operator::Mul, max, 2), Ok(max));
+        assert_eq!(Arithmetic::Saturating.apply(Operator::Sub, i64::min_value(), 1), Ok(i64::min_value()));
+        assert_eq!(Arithmetic::Checked.apply(Operator::Mul, max, 2), Err(ArithmeticError::Overflow));
+        assert_eq!(Arithmetic::Checked.apply(Operator::Mod, -7, 3), Ok(-1));
+        assert_eq!(Arithmetic::Wrapping.apply(Operator::Mod, 7, 0), Err(ArithmeticError::DivisionByZero));
+        assert_eq!(Arithmetic::parse("unbounded"), Some(Arithmetic::Unbounded));
+        assert_eq!(Arithmetic::parse("exact"), None);
+    }
+
+    #[test]
+    fn test_big_int() {
+        let max = BigInt::from(i64::max_value());
+        let squared = max.mul(&max);
+        assert_eq!(squared.to_string(), "85070591730234615847396907784232501249");
+        assert_eq!(squared.to_i64(), None);
+        assert_eq!(squared.saturate(), i64::max_value());
+        assert_eq!("85070591730234615847396907784232501249".parse::<BigInt>(), Ok(squared.clone()));
+        assert_eq!(squared.sub(&squared).add(&BigInt::from(-5)).to_i64(), Some(-5));
+        assert_eq!(squared.rem(&max).map(|r| r.to_i64()), Some(Some(0)));
+        assert_eq!(squared.add(&BigInt::from(3)).rem(&max).map(|r| r.to_i64()), Some(Some(3)));
+        assert_eq!(BigInt::from(-7).rem(&BigInt::from(3)), Some(BigInt::from(-1)));
+        assert_eq!(BigInt::from(i64::min_value()).to_i64(), Some(i64::min_value()));
+        assert_eq!(BigInt::from(i64::min_value()).sub(&BigInt::from(1)).saturate(), i64::min_value());
+        assert_eq!(BigInt::from(-12).to_string(), "-12");
+        assert_eq!(BigInt::from(1_000_000_000).mul(&BigInt::from(1_000_000_007)).to_string(), "1000000007000000000");
+    }
+}
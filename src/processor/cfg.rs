@@ -0,0 +1,251 @@
+use super::{RegisterTable, Value};
+use super::instruction::{self, Instruction, InstructionType};
+use std::collections::BTreeSet;
+
+/// Where execution can go after a basic block
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Successor {
+    /// The block starting at this PC
+    Pc(usize),
+    /// The PC leaves the program, halting it
+    Exit,
+    /// A jump by the value of a register, which can't be known without running the program
+    Dynamic,
+}
+
+/// A run of instructions that is only entered at its first instruction and only left after its last
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Block {
+    pub start: usize,
+    /// One past the last instruction of the block
+    pub end: usize,
+    /// Where the jump ending the block goes, if it ends with a jump that may be taken
+    pub taken: Option<Successor>,
+    /// Where execution continues if the block doesn't jump, None if it always jumps
+    pub next: Option<Successor>,
+}
+
+/// A `jgz`/`jnz` that might be taken
+struct Jump {
+    target: Successor,
+    always: bool,
+}
+
+impl Jump {
+    fn of(pc: usize, instruction: &Instruction, length: usize) -> Option<Jump> {
+        let instruction_type = instruction.get_type();
+        let operands = instruction.get_operands();
+        let (condition, offset) = match (instruction_type, operands.as_slice()) {
+            (InstructionType::Jgz, &[condition, offset]) | (InstructionType::Jnz, &[condition, offset]) => (condition, offset),
+            _ => return None
+        };
+        let always = match condition {
+            Value::Literal(value) if instruction_type == InstructionType::Jgz => value > 0,
+            Value::Literal(value) => value != 0,
+            Value::Register(_) => false,
+        };
+        // A jump whose condition never holds is just a slow no-op
+        if let (Value::Literal(_), false) = (condition, always) {
+            return None;
+        }
+        let target = match offset {
+            Value::Literal(offset) => resolve(pc as i64 + offset, length),
+            Value::Register(_) => Successor::Dynamic,
+        };
+        Some(Jump { target, always })
+    }
+}
+
+fn resolve(pc: i64, length: usize) -> Successor {
+    if pc >= 0 && pc < length as i64 {
+        Successor::Pc(pc as usize)
+    } else {
+        Successor::Exit
+    }
+}
+
+/// The basic blocks of a program and the jumps between them
+pub struct ControlFlowGraph {
+    blocks: Vec<Block>,
+}
+
+impl ControlFlowGraph {
+    /// Splits a program into basic blocks at every jump and jump target
+    pub fn new(instructions: &[Box<Instruction>]) -> ControlFlowGraph {
+        let length = instructions.len();
+        let jumps = instructions.iter().enumerate()
+            .map(|(pc, instruction)| Jump::of(pc, &**instruction, length))
+            .collect::<Vec<Option<Jump>>>();
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (pc, jump) in jumps.iter().enumerate() {
+            if let Some(ref jump) = *jump {
+                leaders.insert(pc + 1);
+                if let Successor::Pc(target) = jump.target {
+                    leaders.insert(target);
+                }
+            }
+        }
+        leaders.insert(length);
+        let leaders = leaders.into_iter().filter(|&pc| pc <= length).collect::<Vec<usize>>();
+
+        let blocks = leaders.windows(2).map(|bounds| {
+            let (start, end) = (bounds[0], bounds[1]);
+            let fall_through = resolve(end as i64, length);
+            match jumps[end - 1] {
+                Some(ref jump) => Block { start, end, taken: Some(jump.target), next: if jump.always { None } else { Some(fall_through) } },
+                None => Block { start, end, taken: None, next: Some(fall_through) }
+            }
+        }).collect();
+        ControlFlowGraph { blocks }
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Checks whether any block ends with a jump through a register
+    pub fn has_dynamic_jumps(&self) -> bool {
+        self.blocks.iter().any(|block| block.taken == Some(Successor::Dynamic))
+    }
+
+    /// Formats the instruction at `pc`, with a static jump target replaced by the label of its block
+    fn format(&self, pc: usize, instruction: &Instruction, registers: &RegisterTable) -> String {
+        let operands = instruction.get_operands();
+        let target = self.blocks.iter().find(|block| block.end == pc + 1).and_then(|block| block.taken);
+        match (target, operands.as_slice()) {
+            (Some(Successor::Pc(target)), &[condition, _]) => format!("{} {} {}", instruction.get_type().get_name(), condition.format(registers), label(target)),
+            _ => instruction::disassemble(instruction, registers)
+        }
+    }
+
+    /// Formats the program as a listing with a label at the start of each block and jump targets
+    ///  resolved to those labels. Each instruction is commented with its PC, and jumps out of
+    ///  the program or through a register are flagged.
+    /// The listing parses back into the same program
+    pub fn listing(&self, instructions: &[Box<Instruction>], registers: &RegisterTable) -> String {
+        let mut output = Vec::new();
+        for block in self.blocks.iter() {
+            output.push(format!("{}:", label(block.start)));
+            for pc in block.start..block.end {
+                let mut comment = pc.to_string();
+                if pc + 1 == block.end {
+                    match block.taken {
+                        Some(Successor::Exit) => comment.push_str(", exits"),
+                        Some(Successor::Dynamic) => comment.push_str(", dynamic jump"),
+                        _ => {}
+                    }
+                }
+                output.push(format!("    {:<20} # {}", self.format(pc, &*instructions[pc], registers), comment));
+            }
+        }
+        output.join("\n")
+    }
+
+    /// Formats the graph in the Graphviz DOT language, with one node per block
+    pub fn to_dot(&self, instructions: &[Box<Instruction>], registers: &RegisterTable) -> String {
+        let mut output = vec![String::from("digraph program {"), String::from("    node [shape=box, fontname=\"monospace\"];")];
+        output.push(String::from("    exit [shape=doublecircle];"));
+        if self.has_dynamic_jumps() {
+            output.push(String::from("    dynamic [shape=diamond, label=\"?\"];"));
+        }
+        for block in self.blocks.iter() {
+            let lines = (block.start..block.end)
+                .map(|pc| format!("{}: {}\\l", pc, instruction::disassemble(&*instructions[pc], registers)))
+                .collect::<Vec<String>>();
+            output.push(format!("    {} [label=\"{}\"];", label(block.start), lines.concat()));
+        }
+        for block in self.blocks.iter() {
+            let conditional = block.taken.is_some() && block.next.is_some();
+            if let Some(taken) = block.taken {
+                let style = match (taken, conditional) {
+                    (Successor::Dynamic, _) => " [style=dashed]",
+                    (_, true) => " [label=\"taken\"]",
+                    _ => ""
+                };
+                output.push(format!("    {} -> {}{};", label(block.start), node(taken), style));
+            }
+            if let Some(next) = block.next {
+                let style = if conditional { " [label=\"not taken\"]" } else { "" };
+                output.push(format!("    {} -> {}{};", label(block.start), node(next), style));
+            }
+        }
+        output.push(String::from("}"));
+        output.join("\n")
+    }
+}
+
+fn label(pc: usize) -> String {
+    format!("L{}", pc)
+}
+
+fn node(successor: Successor) -> String {
+    match successor {
+        Successor::Pc(pc) => label(pc),
+        Successor::Exit => String::from("exit"),
+        Successor::Dynamic => String::from("dynamic"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INSTRUCTION_SET: [InstructionType; 5] = [
+        InstructionType::Set,
+        InstructionType::Sub,
+        InstructionType::Rcv,
+        InstructionType::Jgz,
+        InstructionType::Jnz
+    ];
+
+    fn parse(input: &str) -> (Vec<Box<Instruction>>, RegisterTable) {
+        let mut registers = RegisterTable::new();
+        let instructions = instruction::parse_program(input, &INSTRUCTION_SET, &mut registers).unwrap();
+        (instructions, registers)
+    }
+
+    #[test]
+    fn test_blocks() {
+        let (instructions, _) = parse("set a 3\nsub a 1\njnz a -1\njnz 0 5\njgz a b\njnz 1 -6");
+        let graph = ControlFlowGraph::new(&instructions);
+        assert_eq!(graph.blocks(), &[
+            Block { start: 0, end: 1, taken: None, next: Some(Successor::Pc(1)) },
+            Block { start: 1, end: 3, taken: Some(Successor::Pc(1)), next: Some(Successor::Pc(3)) },
+            Block { start: 3, end: 5, taken: Some(Successor::Dynamic), next: Some(Successor::Pc(5)) },
+            Block { start: 5, end: 6, taken: Some(Successor::Exit), next: None },
+        ][..]);
+        assert!(graph.has_dynamic_jumps());
+    }
+
+    #[test]
+    fn test_listing() {
+        let input = "set a 3\nsub a 1\njnz a -1\nrcv a\njgz a b\njnz 1 -6";
+        let (instructions, registers) = parse(input);
+        let listing = ControlFlowGraph::new(&instructions).listing(&instructions, &registers);
+        assert_eq!(listing, "\
+L0:
+    set a 3              # 0
+L1:
+    sub a 1              # 1
+    jnz a L1             # 2
+L3:
+    rcv a                # 3
+    jgz a b              # 4, dynamic jump
+L5:
+    jnz 1 -6             # 5, exits");
+        let (reparsed, _) = parse(&listing);
+        assert_eq!(format!("{:?}", reparsed), format!("{:?}", instructions));
+    }
+
+    #[test]
+    fn test_dot() {
+        let (instructions, registers) = parse("set a 3\nsub a 1\njnz a -1");
+        let dot = ControlFlowGraph::new(&instructions).to_dot(&instructions, &registers);
+        assert!(dot.contains("    L1 [label=\"1: sub a 1\\l2: jnz a -1\\l\"];"));
+        assert!(dot.contains("    L0 -> L1;"));
+        assert!(dot.contains("    L1 -> L1 [label=\"taken\"];"));
+        assert!(dot.contains("    L1 -> exit [label=\"not taken\"];"));
+        assert!(!dot.contains("dynamic"));
+    }
+}
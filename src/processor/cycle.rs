@@ -0,0 +1,108 @@
+use super::environment::Environment;
+use super::arithmetic::BigInt;
+use std::fmt::{self, Display, Formatter};
+
+/// A loop a machine can't leave: it came back to a state it was in `period` steps before, having only
+///  executed the instructions from `first` to `last` in between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub first: i64,
+    pub last: i64,
+    pub period: u64,
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "infinite loop detected at pc {}..{} with a period of {} steps", self.first, self.last, self.period)
+    }
+}
+
+/// Everything that decides what a machine does next
+#[derive(PartialEq, Eq)]
+struct State {
+    pc: i64,
+    registers: Vec<i64>,
+    /// The exact value of every register, only kept once a register has outgrown an i64
+    exact: Vec<BigInt>,
+    queue: Vec<i64>,
+}
+
+impl State {
+    fn of(environment: &Environment) -> State {
+        let exact = match environment.has_wide_registers() {
+            true => (0..environment.register_values().len()).map(|slot| environment.get_exact_register(slot)).collect(),
+            false => Vec::new()
+        };
+        State { pc: environment.get_pc(), registers: environment.register_values().to_vec(), exact, queue: environment.queue() }
+    }
+}
+
+/// Finds infinite loops in a deterministic machine with Brent's algorithm, which only ever keeps one
+///  earlier state. A loop is found within twice the number of steps it takes to enter it and go round once.
+/// A machine that receives values from anywhere but its own queue isn't deterministic, and
+///  may leave a state it has been in before
+pub struct CycleDetector {
+    /// The state being waited on to come round again
+    saved: Option<State>,
+    /// How many steps `saved` is waited on before the current state replaces it, doubling each time
+    power: u64,
+    /// The number of steps since `saved`
+    steps: u64,
+    /// The smallest and largest PC executed since `saved`
+    first: i64,
+    last: i64,
+}
+
+impl CycleDetector {
+    pub fn new() -> CycleDetector {
+        CycleDetector { saved: None, power: 1, steps: 0, first: 0, last: 0 }
+    }
+
+    /// Records the state of `environment` before its next step.
+    /// Returns the loop it is stuck in once it comes back to the saved state
+    pub fn observe(&mut self, environment: &Environment) -> Option<Cycle> {
+        let state = State::of(environment);
+        if self.saved.as_ref() == Some(&state) {
+            return Some(Cycle { first: self.first, last: self.last, period: self.steps });
+        }
+        let pc = state.pc;
+        if self.saved.is_none() || self.steps == self.power {
+            if self.saved.is_some() {
+                self.power *= 2;
+            }
+            self.saved = Some(state);
+            self.steps = 0;
+            self.first = pc;
+            self.last = pc;
+        }
+        self.first = self.first.min(pc);
+        self.last = self.last.max(pc);
+        self.steps += 1;
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_observe() {
+        let mut environment = Environment::new();
+        let mut detector = CycleDetector::new();
+        // Counts a from 0 to 2 and back to 0 over pcs 4 to 6, after starting at pc 3
+        let states = [(4, 0), (5, 1), (6, 2)];
+        environment.set_pc(3);
+        assert_eq!(detector.observe(&environment), None);
+        let cycle = states.iter().cycle().take(20).filter_map(|&(pc, a)| {
+            environment.set_pc(pc);
+            environment.set(&"a", a);
+            detector.observe(&environment)
+        }).next();
+        assert_eq!(cycle, Some(Cycle { first: 4, last: 6, period: 3 }));
+
+        // Values waiting to be received are part of the state
+        environment.enqueue(1);
+        assert_eq!(detector.observe(&environment), None);
+    }
+}
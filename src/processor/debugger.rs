@@ -0,0 +1,272 @@
+use super::instruction::InstructionType;
+use super::environment::SpecialRegister;
+use super::watchdog::{Fault, Limits, Watchdog};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+/// A machine that can be driven one instruction at a time
+pub trait Debuggable {
+    /// Executes the instruction at the current PC.
+    /// Returns the type of the instruction (`Halt` if the PC has left the program) and its result
+    fn step(&mut self) -> (InstructionType, Option<i64>);
+    fn get_pc(&self) -> i64;
+    /// Gets the name and value of every register
+    fn registers(&self) -> Vec<(String, i64)>;
+    fn set_register(&mut self, register: &str, value: i64);
+    /// Formats the instruction at `pc`, None if `pc` is outside the program
+    fn disassemble(&self, pc: i64) -> Option<String>;
+    /// Takes the error of the last instruction that failed, if any, as a fault of the instruction at `pc`
+    fn take_fault(&mut self, pc: i64) -> Option<Fault>;
+}
+
+/// The reason a debugger handed control back to the user
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// A single instruction was executed
+    Stepped,
+    /// The PC reached a breakpoint
+    Breakpoint(i64),
+    /// The PC left the program
+    Halted,
+    /// The program is waiting on a `rcv` with nothing to receive
+    Blocked,
+    /// A sound was recovered
+    Recovered(i64),
+    /// An instruction failed or execution exceeded its limits
+    Interrupted(Fault),
+}
+
+/// Runs `Debuggable` machines until they reach a breakpoint
+pub struct Debugger {
+    breakpoints: BTreeSet<i64>,
+    limits: Limits,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger { breakpoints: BTreeSet::new(), limits: Limits::none() }
+    }
+
+    /// Stops each `resume` once it exceeds `limits`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Adds a breakpoint at `pc`. Returns false if it already existed
+    pub fn add_breakpoint(&mut self, pc: i64) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    /// Removes the breakpoint at `pc`. Returns false if there was none
+    pub fn remove_breakpoint(&mut self, pc: i64) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> Vec<i64> {
+        self.breakpoints.iter().cloned().collect()
+    }
+
+    /// Executes a single instruction.
+    /// An instruction that fails is left at the PC
+    pub fn step(&self, machine: &mut Debuggable) -> Stop {
+        let pc = machine.get_pc();
+        let result = machine.step();
+        if let Some(fault) = machine.take_fault(pc) {
+            machine.set_register(SpecialRegister::PC.get_name(), pc);
+            return Stop::Interrupted(fault);
+        }
+        match result {
+            (InstructionType::Halt, _) => Stop::Halted,
+            (InstructionType::Rcv, None) => Stop::Blocked,
+            (InstructionType::Recover, Some(frequency)) => Stop::Recovered(frequency),
+            _ => Stop::Stepped
+        }
+    }
+
+    /// Executes instructions until the machine halts, blocks, recovers a sound, fails, exceeds its limits
+    ///  or reaches a breakpoint.
+    /// At least one instruction is always executed so that resuming from a breakpoint makes progress
+    pub fn resume(&self, machine: &mut Debuggable) -> Stop {
+        let mut watchdog = Watchdog::new(self.limits);
+        loop {
+            if let Err(fault) = watchdog.tick() {
+                return Stop::Interrupted(fault);
+            }
+            let stop = self.step(machine);
+            if stop != Stop::Stepped {
+                return stop;
+            }
+            let pc = machine.get_pc();
+            if self.breakpoints.contains(&pc) {
+                return Stop::Breakpoint(pc);
+            }
+        }
+    }
+}
+
+const HELP: &'static str = "\
+commands:
+  break <pc>          set a breakpoint
+  delete <pc>         remove a breakpoint
+  step [<count>]      execute <count> instructions (default 1)
+  continue            run until a breakpoint, halt, blocking rcv, recovered sound or fault
+  regs                print the pc and registers
+  set <reg> <value>   modify a register (`pc` moves the program counter)
+  switch <program>    debug a different program
+  list                print the breakpoints
+  quit";
+
+/// Runs an interactive debugging session over `machines`, reading commands from `input`
+///  and writing results to `output`. Breakpoints are shared by all of the machines, and each
+///  `continue` is held to `limits`
+pub fn run_session<R, W>(mut machines: Vec<&mut Debuggable>, limits: Limits, input: R, output: &mut W) -> io::Result<()>
+    where R: BufRead, W: Write {
+    let mut debugger = Debugger::new();
+    debugger.set_limits(limits);
+    let mut current = 0;
+    if machines.is_empty() {
+        return Ok(());
+    }
+    print_location(&*machines[current], output)?;
+    write!(output, "[{}]> ", current)?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let parts = line.split_whitespace().collect::<Vec<&str>>();
+        match parts.as_slice() {
+            [] => {},
+            ["break", pc] | ["b", pc] => match pc.parse::<i64>() {
+                Ok(pc) => {
+                    debugger.add_breakpoint(pc);
+                    writeln!(output, "breakpoint set at {}", pc)?;
+                },
+                Err(_) => writeln!(output, "invalid pc: {}", pc)?
+            },
+            ["delete", pc] | ["d", pc] => match pc.parse::<i64>() {
+                Ok(pc) if debugger.remove_breakpoint(pc) => writeln!(output, "breakpoint removed at {}", pc)?,
+                _ => writeln!(output, "no breakpoint at {}", pc)?
+            },
+            ["step"] | ["s"] => {
+                let stop = debugger.step(machines[current]);
+                print_stop(&stop, &*machines[current], output)?;
+            },
+            ["step", count] | ["s", count] => match count.parse::<usize>() {
+                Ok(count) => {
+                    let mut stop = Stop::Stepped;
+                    for _ in 0..count {
+                        stop = debugger.step(machines[current]);
+                        if stop != Stop::Stepped {
+                            break;
+                        }
+                    }
+                    print_stop(&stop, &*machines[current], output)?;
+                },
+                Err(_) => writeln!(output, "invalid count: {}", count)?
+            },
+            ["continue"] | ["c"] => {
+                let stop = debugger.resume(machines[current]);
+                print_stop(&stop, &*machines[current], output)?;
+            },
+            ["regs"] | ["r"] => {
+                writeln!(output, "pc = {}", machines[current].get_pc())?;
+                for (name, value) in machines[current].registers() {
+                    writeln!(output, "{} = {}", name, value)?;
+                }
+            },
+            ["set", register, value] => match value.parse::<i64>() {
+                Ok(value) => {
+                    machines[current].set_register(register, value);
+                    writeln!(output, "{} = {}", register, value)?;
+                },
+                Err(_) => writeln!(output, "invalid value: {}", value)?
+            },
+            ["switch", program] => match program.parse::<usize>() {
+                Ok(program) if program < machines.len() => {
+                    current = program;
+                    print_location(&*machines[current], output)?;
+                },
+                _ => writeln!(output, "no program {}, there are {}", program, machines.len())?
+            },
+            ["list"] | ["l"] => writeln!(output, "breakpoints: {:?}", debugger.breakpoints())?,
+            ["quit"] | ["q"] => return Ok(()),
+            _ => writeln!(output, "{}", HELP)?
+        }
+        write!(output, "[{}]> ", current)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Prints the instruction the machine will execute next
+fn print_location<W: Write>(machine: &Debuggable, output: &mut W) -> io::Result<()> {
+    let pc = machine.get_pc();
+    match machine.disassemble(pc) {
+        Some(instruction) => writeln!(output, "{}: {}", pc, instruction),
+        None => writeln!(output, "{}: <outside program>", pc)
+    }
+}
+
+fn print_stop<W: Write>(stop: &Stop, machine: &Debuggable, output: &mut W) -> io::Result<()> {
+    match *stop {
+        Stop::Stepped => {},
+        Stop::Breakpoint(pc) => writeln!(output, "breakpoint at {}", pc)?,
+        Stop::Halted => writeln!(output, "halted")?,
+        Stop::Blocked => writeln!(output, "blocked on rcv")?,
+        Stop::Recovered(frequency) => writeln!(output, "recovered {}", frequency)?,
+        Stop::Interrupted(fault) => writeln!(output, "interrupted: {}", fault)?,
+    }
+    print_location(machine, output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use day23::Coprocessor;
+    use day18::Interpreter;
+    use processor::arithmetic::{Arithmetic, ArithmeticError};
+
+    #[test]
+    fn test_resume_to_breakpoint() {
+        let mut coprocessor = Coprocessor::new("set a 3\nsub a 1\njnz a -1\nset b 1").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(2);
+        assert_eq!(debugger.resume(&mut coprocessor), Stop::Breakpoint(2));
+        assert_eq!(coprocessor.registers(), vec![(String::from("a"), 2), (String::from("b"), 0)]);
+        assert_eq!(debugger.resume(&mut coprocessor), Stop::Breakpoint(2));
+        debugger.remove_breakpoint(2);
+        assert_eq!(debugger.resume(&mut coprocessor), Stop::Halted);
+        assert_eq!(coprocessor.registers(), vec![(String::from("a"), 0), (String::from("b"), 1)]);
+    }
+
+    #[test]
+    fn test_session() {
+        let mut coprocessor = Coprocessor::new("set a 3\nsub a 1\njnz a -1\nset b 1").unwrap();
+        let input = "break 3\ncontinue\nset a 7\nregs\nstep\nstep\n";
+        let mut output = Vec::new();
+        run_session(vec![&mut coprocessor], Limits::none(), input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("breakpoint at 3\n3: set b 1"));
+        assert!(output.contains("pc = 3\na = 7\nb = 0"));
+        assert!(output.contains("halted\n4: <outside program>"));
+    }
+
+    #[test]
+    fn test_resume_stops() {
+        // Recovering a sound stops, rather than looping forever
+        let mut interpreter = Interpreter::sound("set a 1\nsnd 440\nrcv a\njgz a -2").unwrap();
+        let debugger = Debugger::new();
+        assert_eq!(debugger.resume(interpreter.programs_mut()[0]), Stop::Recovered(440));
+
+        // So does exceeding the limits
+        let mut coprocessor = Coprocessor::new("set a 1\njnz a 0").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.set_limits(Limits::steps(10));
+        assert_eq!(debugger.resume(&mut coprocessor), Stop::Interrupted(Fault::StepLimit(10)));
+
+        // A failed instruction is left at the PC
+        let mut coprocessor = Coprocessor::new("set a 9223372036854775807\nmul a 2").unwrap();
+        coprocessor.set_arithmetic(Arithmetic::Checked);
+        assert_eq!(debugger.resume(&mut coprocessor), Stop::Interrupted(Fault::Arithmetic { pc: 1, error: ArithmeticError::Overflow }));
+        assert_eq!(coprocessor.get_pc(), 1);
+    }
+}
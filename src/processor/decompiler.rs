@@ -0,0 +1,534 @@
+use super::{RegisterTable, Value};
+use super::instruction::{Instruction, InstructionType};
+use super::cfg::ControlFlowGraph;
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Mod,
+}
+
+impl Operator {
+    fn symbol(&self) -> &'static str {
+        match *self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Mod => "%",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match *self {
+            Operator::Add | Operator::Sub => 1,
+            Operator::Mul | Operator::Mod => 2,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Expression {
+    Value(Value),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Formats the expression, returning it along with the precedence of its outermost operator
+    fn format(&self, registers: &RegisterTable) -> (String, u8) {
+        match *self {
+            Expression::Value(ref value) => (value.format(registers), 3),
+            Expression::Binary(operator, ref left, ref right) => {
+                let precedence = operator.precedence();
+                let (left, left_precedence) = left.format(registers);
+                let (right, right_precedence) = right.format(registers);
+                let left = if left_precedence < precedence { format!("({})", left) } else { left };
+                let right = if right_precedence <= precedence { format!("({})", right) } else { right };
+                (format!("{} {} {}", left, operator.symbol(), right), precedence)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Comparison {
+    NotZero,
+    Zero,
+    Positive,
+    NotPositive,
+}
+
+/// The condition under which a jump is taken
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Condition {
+    expression: Expression,
+    comparison: Comparison,
+}
+
+impl Condition {
+    fn negate(&self) -> Condition {
+        let comparison = match self.comparison {
+            Comparison::NotZero => Comparison::Zero,
+            Comparison::Zero => Comparison::NotZero,
+            Comparison::Positive => Comparison::NotPositive,
+            Comparison::NotPositive => Comparison::Positive,
+        };
+        Condition { expression: self.expression.clone(), comparison }
+    }
+
+    fn format(&self, registers: &RegisterTable) -> String {
+        let symbol = match self.comparison {
+            Comparison::NotZero => "!=",
+            Comparison::Zero => "==",
+            Comparison::Positive => ">",
+            Comparison::NotPositive => "<=",
+        };
+        // Compare the two sides of a subtraction directly, `a - b != 0` reads better as `a != b`
+        match self.expression {
+            Expression::Binary(Operator::Sub, ref left, ref right) => format!("{} {} {}", left.format(registers).0, symbol, right.format(registers).0),
+            ref expression => format!("{} {} 0", expression.format(registers).0, symbol),
+        }
+    }
+}
+
+/// Where a jump goes
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Target {
+    /// An absolute PC, possibly outside the program
+    Static(i64),
+    /// The jump's own PC plus the value of a register
+    Dynamic(usize, Value),
+}
+
+/// One or more instructions that act as a single operation
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Operation {
+    Assign(usize, Expression),
+    Send(Expression),
+    Receive(usize),
+    Play(Expression),
+    Recover(Expression),
+    /// A jump, taken if `condition` holds or always if there is no condition
+    Jump { condition: Option<Condition>, target: Target, pc: usize },
+    /// A jump that is never taken
+    Nop,
+}
+
+/// Structured pseudocode
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Statement {
+    Assign(usize, Expression),
+    Send(Expression),
+    Receive(usize),
+    Play(Expression),
+    Recover(Expression),
+    If(Condition, Vec<Statement>, Vec<Statement>),
+    DoWhile(Vec<Statement>, Condition),
+    Loop(Vec<Statement>),
+    Break,
+    Continue,
+    Halt,
+    Goto(i64),
+    GotoDynamic(usize, Value),
+}
+
+/// An instruction that only writes to a register, as the register, the operator applied to it
+///  (None for `set`) and the operand
+fn update(instruction: &Instruction) -> Option<(usize, Option<Operator>, Value)> {
+    let operands = instruction.get_operands();
+    let (register, value) = match operands.as_slice() {
+        &[Value::Register(register), value] => (register, value),
+        _ => return None
+    };
+    let operator = match instruction.get_type() {
+        InstructionType::Set => return Some((register, None, value)),
+        InstructionType::Add => Operator::Add,
+        InstructionType::Sub => Operator::Sub,
+        InstructionType::Mul => Operator::Mul,
+        InstructionType::Mod => Operator::Mod,
+        _ => return None
+    };
+    // Adding a negative literal is subtracting a positive one, and vice versa
+    match (operator, value) {
+        (Operator::Add, Value::Literal(literal)) if literal < 0 && literal != i64::min_value() => Some((register, Some(Operator::Sub), Value::Literal(-literal))),
+        (Operator::Sub, Value::Literal(literal)) if literal < 0 && literal != i64::min_value() => Some((register, Some(Operator::Add), Value::Literal(-literal))),
+        _ => Some((register, Some(operator), value))
+    }
+}
+
+/// A run of instructions that only update one register, with nothing jumping into its middle
+struct Chain {
+    register: usize,
+    expression: Expression,
+    /// True if the chain starts with a `set`, i.e. it doesn't depend on the register's old value
+    fresh: bool,
+    start: usize,
+    end: usize,
+}
+
+fn chain(instructions: &[Box<Instruction>], leaders: &BTreeSet<usize>, start: usize) -> Option<Chain> {
+    let (register, operator, value) = update(&*instructions[start])?;
+    let fresh = operator.is_none();
+    let mut expression = Expression::Value(Value::Register(register));
+    let mut end = start;
+    let mut next = Some((register, operator, value));
+    while let Some((next_register, operator, value)) = next {
+        if next_register != register || (end > start && leaders.contains(&end)) {
+            break;
+        }
+        let operand = if value == Value::Register(register) { expression.clone() } else { Expression::Value(value) };
+        expression = match operator {
+            Some(operator) => Expression::Binary(operator, Box::new(expression), Box::new(operand)),
+            None => operand
+        };
+        end += 1;
+        next = instructions.get(end).and_then(|instruction| update(&**instruction));
+    }
+    Some(Chain { register, expression, fresh, start, end })
+}
+
+/// Gets the condition (None if it always jumps) and target of a jump.
+/// Returns Some(None) for a jump that is never taken
+fn jump(pc: usize, instruction: &Instruction) -> Option<Option<(Option<Condition>, Target)>> {
+    let instruction_type = instruction.get_type();
+    let operands = instruction.get_operands();
+    let (condition, offset) = match (instruction_type, operands.as_slice()) {
+        (InstructionType::Jgz, &[condition, offset]) | (InstructionType::Jnz, &[condition, offset]) => (condition, offset),
+        _ => return None
+    };
+    let comparison = if instruction_type == InstructionType::Jgz { Comparison::Positive } else { Comparison::NotZero };
+    let condition = match condition {
+        Value::Literal(value) if (comparison == Comparison::Positive && value > 0) || (comparison == Comparison::NotZero && value != 0) => None,
+        Value::Literal(_) => return Some(None),
+        register => Some(Condition { expression: Expression::Value(register), comparison }),
+    };
+    let target = match offset {
+        Value::Literal(offset) => Target::Static(pc as i64 + offset),
+        register => Target::Dynamic(pc, register),
+    };
+    Some(Some((condition, target)))
+}
+
+/// Registers that are only ever set in order to be tested by the jump right after, e.g. `g` in
+///  `set g d; sub g b; jnz g 2`. These can be folded into the condition of the jump
+fn scratch_registers(instructions: &[Box<Instruction>], leaders: &BTreeSet<usize>) -> HashSet<usize> {
+    let mut tested = HashSet::new();
+    let mut read = HashSet::new();
+    let mut pc = 0;
+    while pc < instructions.len() {
+        if let Some(chain) = chain(instructions, leaders, pc) {
+            if !chain.fresh {
+                read.insert(chain.register);
+            }
+            for instruction in instructions[chain.start..chain.end].iter() {
+                read.extend(instruction.get_operands().into_iter().skip(1).filter_map(|operand| match operand {
+                    Value::Register(register) if register != chain.register => Some(register),
+                    _ => None
+                }));
+            }
+            let tests = instructions.get(chain.end).map_or(false, |instruction| {
+                jump(chain.end, &**instruction).is_some()
+                    && instruction.get_operands()[0] == Value::Register(chain.register)
+                    && !leaders.contains(&chain.end)
+            });
+            if tests && chain.fresh {
+                tested.insert(chain.register);
+                read.extend(instructions[chain.end].get_operands().into_iter().skip(1).filter_map(|operand| match operand {
+                    Value::Register(register) => Some(register),
+                    _ => None
+                }));
+                pc = chain.end + 1;
+            } else {
+                pc = chain.end;
+            }
+            continue;
+        }
+        let instruction = &instructions[pc];
+        let operands = instruction.get_operands();
+        // `rcv` only writes to its register
+        let reads = if instruction.get_type() == InstructionType::Rcv { &operands[1..] } else { &operands[..] };
+        read.extend(reads.iter().filter_map(|operand| match *operand {
+            Value::Register(register) => Some(register),
+            _ => None
+        }));
+        pc += 1;
+    }
+    tested.difference(&read).cloned().collect()
+}
+
+/// Groups instructions into operations, returning each with the PC of the instruction that follows it
+fn operations(instructions: &[Box<Instruction>], leaders: &BTreeSet<usize>) -> Vec<(usize, Operation, usize)> {
+    let scratch = scratch_registers(instructions, leaders);
+    let mut operations = Vec::new();
+    let mut pc = 0;
+    while pc < instructions.len() {
+        if let Some(chain) = chain(instructions, leaders, pc) {
+            let tested = instructions.get(chain.end).and_then(|instruction| jump(chain.end, &**instruction));
+            match tested {
+                Some(Some((Some(condition), target))) if chain.fresh && scratch.contains(&chain.register) && !leaders.contains(&chain.end) => {
+                    let condition = Condition { expression: chain.expression, comparison: condition.comparison };
+                    operations.push((chain.start, Operation::Jump { condition: Some(condition), target, pc: chain.end }, chain.end + 1));
+                    pc = chain.end + 1;
+                },
+                _ => {
+                    operations.push((chain.start, Operation::Assign(chain.register, chain.expression), chain.end));
+                    pc = chain.end;
+                }
+            }
+            continue;
+        }
+        let instruction = &instructions[pc];
+        let operation = match (instruction.get_type(), jump(pc, &**instruction), instruction.get_operands().as_slice()) {
+            (_, Some(Some((condition, target))), _) => Operation::Jump { condition, target, pc },
+            (_, Some(None), _) => Operation::Nop,
+            (InstructionType::Snd, _, &[value]) => Operation::Send(Expression::Value(value)),
+            (InstructionType::Rcv, _, &[Value::Register(register)]) => Operation::Receive(register),
+            (InstructionType::Play, _, &[value]) => Operation::Play(Expression::Value(value)),
+            (InstructionType::Recover, _, &[value]) => Operation::Recover(Expression::Value(value)),
+            _ => Operation::Nop
+        };
+        operations.push((pc, operation, pc + 1));
+        pc += 1;
+    }
+    operations
+}
+
+/// Recovers structured statements from a flat list of operations
+struct Structurer {
+    operations: Vec<(usize, Operation, usize)>,
+    length: usize,
+}
+
+/// The innermost loop being structured: `continue` jumps to `start`, `break` jumps to `end`
+#[derive(Clone, Copy)]
+struct Enclosing {
+    start: usize,
+    end: usize,
+}
+
+impl Structurer {
+    fn at(&self, pc: usize) -> Option<&(usize, Operation, usize)> {
+        self.operations.iter().find(|&&(start, _, _)| start == pc)
+    }
+
+    /// Finds the last operation in [start, end) jumping back to `start`, returning its index
+    fn loop_back(&self, start: usize, end: usize) -> Option<usize> {
+        self.operations.iter().rposition(|&(from, ref operation, _)| match *operation {
+            Operation::Jump { target: Target::Static(target), pc, .. } => from >= start && pc < end && target == start as i64,
+            _ => false
+        })
+    }
+
+    /// Wraps `statement` in an `if` when it is conditional
+    fn guard(condition: &Option<Condition>, statement: Statement) -> Statement {
+        match *condition {
+            Some(ref condition) => Statement::If(condition.clone(), vec![statement], Vec::new()),
+            None => statement
+        }
+    }
+
+    /// Structures the operations in [start, end)
+    fn structure(&self, start: usize, end: usize, enclosing: Option<Enclosing>) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        let mut pc = start;
+        while pc < end {
+            if let Some(index) = self.loop_back(pc, end) {
+                let (from, ref operation, next) = self.operations[index];
+                if let Operation::Jump { ref condition, .. } = *operation {
+                    let body = self.structure(pc, from, Some(Enclosing { start: pc, end: next }));
+                    statements.push(match *condition {
+                        Some(ref condition) => Statement::DoWhile(body, condition.clone()),
+                        None => Statement::Loop(body)
+                    });
+                    pc = next;
+                    continue;
+                }
+            }
+            let (_, operation, next) = match self.at(pc) {
+                Some(&(start, ref operation, next)) => (start, operation.clone(), next),
+                None => { pc += 1; continue; }
+            };
+            match operation {
+                Operation::Assign(register, expression) => statements.push(Statement::Assign(register, expression)),
+                Operation::Send(expression) => statements.push(Statement::Send(expression)),
+                Operation::Receive(register) => statements.push(Statement::Receive(register)),
+                Operation::Play(expression) => statements.push(Statement::Play(expression)),
+                Operation::Recover(expression) => statements.push(Statement::Recover(expression)),
+                Operation::Nop => {},
+                Operation::Jump { condition, target: Target::Dynamic(from, value), .. } => {
+                    statements.push(Structurer::guard(&condition, Statement::GotoDynamic(from, value)));
+                },
+                Operation::Jump { condition, target: Target::Static(target), .. } => {
+                    if target >= next as i64 && target <= end as i64 {
+                        let target = target as usize;
+                        // The fall through branch runs when the jump isn't taken
+                        let negated = condition.as_ref().map(Condition::negate);
+                        match (negated, self.else_jump(next, target, end)) {
+                            (Some(negated), Some((else_jump, after))) => {
+                                let then = self.structure(next, else_jump, enclosing);
+                                let otherwise = self.structure(target, after, enclosing);
+                                statements.push(Statement::If(negated, then, otherwise));
+                                pc = after;
+                                continue;
+                            },
+                            (Some(negated), None) => {
+                                let then = self.structure(next, target, enclosing);
+                                statements.push(Statement::If(negated, then, Vec::new()));
+                            },
+                            // Code that is jumped over unconditionally can never run
+                            (None, _) => {}
+                        }
+                        pc = target;
+                        continue;
+                    }
+                    let statement = match enclosing {
+                        Some(enclosing) if target == enclosing.end as i64 => Statement::Break,
+                        Some(enclosing) if target == enclosing.start as i64 => Statement::Continue,
+                        _ if target < 0 || target >= self.length as i64 => Statement::Halt,
+                        _ => Statement::Goto(target)
+                    };
+                    statements.push(Structurer::guard(&condition, statement));
+                }
+            }
+            pc = next;
+        }
+        statements
+    }
+
+    /// Checks whether the branch [start, target) ends with an unconditional jump forward to `after`,
+    ///  making [target, after) an else branch. Returns the PC of the jump and `after`
+    fn else_jump(&self, start: usize, target: usize, end: usize) -> Option<(usize, usize)> {
+        self.operations.iter().filter(|&&(from, _, next)| from >= start && next == target).filter_map(|&(from, ref operation, _)| match *operation {
+            Operation::Jump { condition: None, target: Target::Static(after), .. } if after > target as i64 && after <= end as i64 => Some((from, after as usize)),
+            _ => None
+        }).next()
+    }
+}
+
+fn format_statements(statements: &[Statement], registers: &RegisterTable, depth: usize, output: &mut Vec<String>) {
+    let indent = "    ".repeat(depth);
+    let name = |register: usize| Value::Register(register).format(registers);
+    for statement in statements {
+        match *statement {
+            Statement::Assign(register, ref expression) => match *expression {
+                Expression::Binary(operator, ref left, ref right) if **left == Expression::Value(Value::Register(register)) => {
+                    output.push(format!("{}{} {}= {}", indent, name(register), operator.symbol(), right.format(registers).0));
+                },
+                _ => output.push(format!("{}{} = {}", indent, name(register), expression.format(registers).0))
+            },
+            Statement::Send(ref expression) => output.push(format!("{}send({})", indent, expression.format(registers).0)),
+            Statement::Receive(register) => output.push(format!("{}{} = receive()", indent, name(register))),
+            Statement::Play(ref expression) => output.push(format!("{}play({})", indent, expression.format(registers).0)),
+            Statement::Recover(ref expression) => output.push(format!("{}recover({})", indent, expression.format(registers).0)),
+            Statement::If(ref condition, ref then, ref otherwise) => {
+                // Prefer `if a { x }` to `if !a { } else { x }`
+                let (condition, then, otherwise) = if then.is_empty() { (condition.negate(), otherwise, then) } else { (condition.clone(), then, otherwise) };
+                output.push(format!("{}if {} {{", indent, condition.format(registers)));
+                format_statements(then, registers, depth + 1, output);
+                if !otherwise.is_empty() {
+                    output.push(format!("{}}} else {{", indent));
+                    format_statements(otherwise, registers, depth + 1, output);
+                }
+                output.push(format!("{}}}", indent));
+            },
+            Statement::DoWhile(ref body, ref condition) => {
+                output.push(format!("{}do {{", indent));
+                format_statements(body, registers, depth + 1, output);
+                output.push(format!("{}}} while {}", indent, condition.format(registers)));
+            },
+            Statement::Loop(ref body) => {
+                output.push(format!("{}loop {{", indent));
+                format_statements(body, registers, depth + 1, output);
+                output.push(format!("{}}}", indent));
+            },
+            Statement::Break => output.push(format!("{}break", indent)),
+            Statement::Continue => output.push(format!("{}continue", indent)),
+            Statement::Halt => output.push(format!("{}halt", indent)),
+            Statement::Goto(pc) => output.push(format!("{}goto L{}", indent, pc)),
+            Statement::GotoDynamic(pc, ref value) => output.push(format!("{}goto L{} + {}", indent, pc, value.format(registers))),
+        }
+    }
+}
+
+/// Decompiles a program into structured pseudocode.
+///
+/// Runs of instructions updating a single register are folded into one assignment, and registers
+///  that are only set to be tested by a jump are folded into the jump's condition.
+/// Jumps forward become `if`/`else`, jumps back become `do`/`while` (or `loop` if unconditional)
+///  and jumps out of a loop become `break`/`continue`. Jumps that fit none of these are shown as
+///  `goto` the label the disassembler gives their target
+pub fn decompile(instructions: &[Box<Instruction>], registers: &RegisterTable) -> String {
+    let leaders = ControlFlowGraph::new(instructions).blocks().iter().map(|block| block.start).collect::<BTreeSet<usize>>();
+    let structurer = Structurer { operations: operations(instructions, &leaders), length: instructions.len() };
+    let statements = structurer.structure(0, instructions.len(), None);
+    let mut output = Vec::new();
+    format_statements(&statements, registers, 0, &mut output);
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use processor::instruction;
+    use day18;
+    use day23;
+
+    fn decompile_program(input: &str, instruction_set: &[InstructionType]) -> String {
+        let mut registers = RegisterTable::new();
+        let instructions = instruction::parse_program(input, instruction_set, &mut registers).unwrap();
+        decompile(&instructions, &registers)
+    }
+
+    #[test]
+    fn test_decompile_coprocessor() {
+        let input = "set b 57\nset c b\njnz a 2\njnz 1 5\nmul b 100\nsub b -100000\nset c b\nsub c -17000\n\
+            set f 1\nset d 2\nset e 2\nset g d\nmul g e\nsub g b\njnz g 2\nset f 0\nsub e -1\nset g e\nsub g b\njnz g -8\n\
+            sub d -1\nset g d\nsub g b\njnz g -13\njnz f 2\nsub h -1\nset g b\nsub g c\njnz g 2\njnz 1 3\nsub b -17\njnz 1 -23";
+        assert_eq!(decompile_program(input, &day23::INSTRUCTION_SET), "\
+b = 57
+c = b
+if a != 0 {
+    b = b * 100 + 100000
+    c = b + 17000
+}
+loop {
+    f = 1
+    d = 2
+    do {
+        e = 2
+        do {
+            if d * e == b {
+                f = 0
+            }
+            e += 1
+        } while e != b
+        d += 1
+    } while d != b
+    if f == 0 {
+        h += 1
+    }
+    if b == c {
+        break
+    }
+    b += 17
+}");
+    }
+
+    #[test]
+    fn test_decompile_duet() {
+        let input = "set a 3\nsnd a\nrcv b\nadd a -1\njgz a -3\njgz b c\nset a 2\nmod a b";
+        assert_eq!(decompile_program(input, &day18::INSTRUCTION_SET), "\
+a = 3
+do {
+    send(a)
+    b = receive()
+    a -= 1
+} while a > 0
+if b > 0 {
+    goto L5 + c
+}
+a = 2 % b");
+    }
+}
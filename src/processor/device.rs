@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+/// Why reading from or writing to a device failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceError {
+    Read(io::ErrorKind),
+    Write(io::ErrorKind),
+    /// A word on the given line of the input isn't an integer
+    NotANumber(usize),
+}
+
+impl Display for DeviceError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DeviceError::Read(kind) => write!(f, "could not read input ({:?})", kind),
+            DeviceError::Write(kind) => write!(f, "could not write output ({:?})", kind),
+            DeviceError::NotANumber(line) => write!(f, "input on line {} is not a number", line),
+        }
+    }
+}
+
+impl Error for DeviceError {
+    fn description(&self) -> &str {
+        match *self {
+            DeviceError::Read(_) => "could not read input",
+            DeviceError::Write(_) => "could not write output",
+            DeviceError::NotANumber(_) => "input is not a number",
+        }
+    }
+}
+
+/// Somewhere `rcv` receives values from once nothing is queued
+pub trait InputDevice {
+    /// Reads the next value, None once the input has run out
+    fn read(&mut self) -> Result<Option<i64>, DeviceError>;
+}
+
+/// Somewhere values sent with `snd` are written to
+pub trait OutputDevice {
+    fn write(&mut self, value: i64) -> Result<(), DeviceError>;
+}
+
+/// Reads whitespace separated integers, any number to a line
+pub struct Reader<R: BufRead> {
+    input: R,
+    /// The words of the current line that haven't been read yet
+    words: VecDeque<String>,
+    line: usize,
+}
+
+impl <R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Reader<R> {
+        Reader { input, words: VecDeque::new(), line: 0 }
+    }
+}
+
+impl <R: BufRead> InputDevice for Reader<R> {
+    fn read(&mut self) -> Result<Option<i64>, DeviceError> {
+        while self.words.is_empty() {
+            let mut line = String::new();
+            if self.input.read_line(&mut line).map_err(|error| DeviceError::Read(error.kind()))? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            self.words.extend(line.split_whitespace().map(String::from));
+        }
+        let word = self.words.pop_front().unwrap_or_default();
+        word.parse::<i64>().map(Some).map_err(|_| DeviceError::NotANumber(self.line))
+    }
+}
+
+/// Writes each value on its own line, flushing after every value so that the output
+///  can be read while the program is still running
+pub struct Writer<W: Write> {
+    output: W,
+}
+
+impl <W: Write> Writer<W> {
+    pub fn new(output: W) -> Writer<W> {
+        Writer { output }
+    }
+}
+
+impl <W: Write> OutputDevice for Writer<W> {
+    fn write(&mut self, value: i64) -> Result<(), DeviceError> {
+        writeln!(self.output, "{}", value)
+            .and_then(|_| self.output.flush())
+            .map_err(|error| DeviceError::Write(error.kind()))
+    }
+}
+
+/// Opens the file at `path` as an input device, `-` being stdin
+pub fn open_input(path: &str) -> io::Result<Box<InputDevice>> {
+    Ok(match path {
+        "-" => Box::new(Reader::new(BufReader::new(io::stdin()))),
+        _ => Box::new(Reader::new(BufReader::new(File::open(path)?)))
+    })
+}
+
+/// Creates the file at `path` as an output device, `-` being stdout
+pub fn open_output(path: &str) -> io::Result<Box<OutputDevice>> {
+    Ok(match path {
+        "-" => Box::new(Writer::new(io::stdout())),
+        _ => Box::new(Writer::new(BufWriter::new(File::create(path)?)))
+    })
+}
+
+/// The devices attached to an environment
+#[derive(Default)]
+pub struct Devices {
+    pub input: Option<Box<InputDevice>>,
+    pub output: Option<Box<OutputDevice>>,
+}
+
+impl Debug for Devices {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Devices {{ input: {}, output: {} }}", self.input.is_some(), self.output.is_some())
+    }
+}
+
+/// Devices can't be compared, so environments are only told apart by which devices they have
+impl PartialEq for Devices {
+    fn eq(&self, other: &Devices) -> bool {
+        self.input.is_some() == other.input.is_some() && self.output.is_some() == other.output.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reader() {
+        let mut reader = Reader::new("1 -2\n\n  3\nfour\n".as_bytes());
+        let values = (0..3).map(|_| reader.read()).collect::<Vec<Result<Option<i64>, DeviceError>>>();
+        assert_eq!(values, vec![Ok(Some(1)), Ok(Some(-2)), Ok(Some(3))]);
+        assert_eq!(reader.read(), Err(DeviceError::NotANumber(4)));
+        assert_eq!(reader.read(), Ok(None));
+    }
+}
@@ -1,14 +1,56 @@
-use std::collections::HashMap;
 use super::value::Value;
+use super::register_table::RegisterTable;
+use super::arithmetic::{Arithmetic, ArithmeticError, BigInt, Operator};
+use super::device::{DeviceError, Devices, InputDevice, OutputDevice};
+use super::watchdog::Fault;
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
 
 #[derive(Debug, PartialEq)]
 pub struct Environment {
-    state: HashMap<String, i64>,
-    linked_env: Option<Rc<RefCell<Environment>>>,
+    registers: Vec<i64>,
+    table: RegisterTable,
+    pc: i64,
+    links: Vec<Rc<RefCell<Environment>>>,
     rcv: VecDeque<i64>,
+    arithmetic: Arithmetic,
+    /// The exact values of registers that don't fit in an i64, with unbounded arithmetic.
+    /// Their slots in `registers` hold the value clamped to an i64
+    wide: HashMap<usize, BigInt>,
+    error: Option<ArithmeticError>,
+    devices: Devices,
+    device_error: Option<DeviceError>,
+}
+
+/// The PC and registers of a machine at some point in its execution
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MachineState {
+    pub pc: i64,
+    pub registers: Vec<(String, i64)>,
+}
+
+impl Display for MachineState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let registers = self.registers.iter().map(|&(ref name, value)| format!("{}={}", name, value)).collect::<Vec<String>>();
+        write!(f, "pc {}, registers [{}]", self.pc, registers.join(", "))
+    }
+}
+
+/// Parses a `name=value` assignment to a register, as given on the command line.
+/// The value may be too big for an i64
+pub fn parse_assignment(input: &str) -> Option<(String, BigInt)> {
+    let mut parts = input.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let value = parts.next()?.trim().parse::<BigInt>().ok()?;
+    let valid = name.chars().next().map_or(false, |c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name != SpecialRegister::PC.get_name();
+    if !valid {
+        return None;
+    }
+    Some((String::from(name), value))
 }
 
 /// A register that has special meaning
@@ -28,40 +70,255 @@ impl SpecialRegister {
 /// A store of registers and their values
 impl Environment {
     pub fn new() -> Environment {
-        Environment { state: HashMap::new(), linked_env: None, rcv: VecDeque::new() }
+        Environment::with_registers(RegisterTable::new())
+    }
+
+    /// Creates an environment with a slot for every register in `table`
+    pub fn with_registers(table: RegisterTable) -> Environment {
+        Environment {
+            registers: vec![0; table.len()],
+            table,
+            pc: 0,
+            links: Vec::new(),
+            rcv: VecDeque::new(),
+            arithmetic: Arithmetic::default(),
+            wide: HashMap::new(),
+            error: None,
+            devices: Devices::default(),
+            device_error: None,
+        }
     }
 
-    /// Links the current environment to another to enable sending/receiving
+    /// Sets what happens when arithmetic on a register overflows
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub fn get_arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// Links the current environment to another to enable sending/receiving.
+    /// An environment may be linked to any number of others
     pub fn link(&mut self, other: Rc<RefCell<Environment>>) {
-        self.linked_env = Some(other);
+        self.links.push(other);
+    }
+
+    /// Writes every value sent from this environment to `output`, as well as to any linked environment
+    pub fn attach_output(&mut self, output: Box<OutputDevice>) {
+        self.devices.output = Some(output);
     }
 
-    /// Gets the current value of a register
+    /// Reads values from `input` whenever a value is received and nothing is queued
+    pub fn attach_input(&mut self, input: Box<InputDevice>) {
+        self.devices.input = Some(input);
+    }
+
+    /// Checks whether an input device is attached, in which case a program may receive values nothing sent
+    pub fn has_input_device(&self) -> bool {
+        self.devices.input.is_some()
+    }
+
+    /// Checks whether an input or output device is attached
+    pub fn has_devices(&self) -> bool {
+        self.devices.input.is_some() || self.devices.output.is_some()
+    }
+
+    /// Gets the environments that values sent from this one are queued for
+    pub fn links(&self) -> &[Rc<RefCell<Environment>>] {
+        &self.links
+    }
+
+    /// Gets the current value of a register by name.
+    /// This is slower than `get_register` and is intended for tests and debugging
     pub fn get<T>(&mut self, register: &T) -> i64
-        where T: Into<String> + Clone {
-        let entry = self.state.entry(register.clone().into()).or_insert(0);
-        *entry
+        where T: AsRef<str> {
+        if register.as_ref() == SpecialRegister::PC.get_name() {
+            return self.pc;
+        }
+        let slot = self.table.slot(register.as_ref());
+        self.get_register(slot)
+    }
+
+    /// Gets the current value of the register in `slot`.
+    /// A register that has outgrown an i64 reads as the smallest or largest i64
+    pub fn get_register(&self, slot: usize) -> i64 {
+        self.registers.get(slot).cloned().unwrap_or(0)
     }
 
     /// Gets the current value of a `Value` which may be a literal or a register
-    pub fn get_value(&mut self, value: &Value) -> i64 {
+    pub fn get_value(&self, value: &Value) -> i64 {
         match value {
             &Value::Literal(value) => value,
-            &Value::Register(ref name) => self.get(name)
+            &Value::Register(slot) => self.get_register(slot)
         }
     }
 
-    /// Sets the value of a register
+    /// Sets the value of a register by name.
+    /// This is slower than `set_register` and is intended for tests and debugging
     pub fn set<T>(&mut self, register: &T, value: i64)
-        where T: Into<String> + Clone {
-        let entry = self.state.entry(register.clone().into()).or_insert(0);
-        *entry = value;
+        where T: AsRef<str> {
+        if register.as_ref() == SpecialRegister::PC.get_name() {
+            self.pc = value;
+            return;
+        }
+        let slot = self.table.slot(register.as_ref());
+        self.set_register(slot, value);
+    }
+
+    /// Sets the value of the register in `slot`
+    pub fn set_register(&mut self, slot: usize, value: i64) {
+        if slot >= self.registers.len() {
+            self.registers.resize(slot + 1, 0);
+        }
+        self.registers[slot] = value;
+        if !self.wide.is_empty() {
+            self.wide.remove(&slot);
+        }
+    }
+
+    /// Gets the current value of a `Value` that is used as a number in its own right, like a value to send
+    ///  or a jump offset, rather than just compared.
+    /// A register that has outgrown an i64 can't be used, so an overflow is kept until `take_fault`
+    pub fn get_narrow_value(&mut self, value: &Value) -> Option<i64> {
+        match value {
+            &Value::Register(slot) if self.is_wide(slot) => {
+                self.error = Some(ArithmeticError::Overflow);
+                None
+            },
+            _ => Some(self.get_value(value))
+        }
+    }
+
+    /// Gets the exact value of the register in `slot`, even if it doesn't fit in an i64
+    pub fn get_exact_register(&self, slot: usize) -> BigInt {
+        self.wide.get(&slot).cloned().unwrap_or_else(|| BigInt::from(self.get_register(slot)))
+    }
+
+    /// Gets the exact value of a `Value` which may be a literal or a register
+    pub fn get_exact_value(&self, value: &Value) -> BigInt {
+        match value {
+            &Value::Literal(value) => BigInt::from(value),
+            &Value::Register(slot) => self.get_exact_register(slot)
+        }
+    }
+
+    /// Sets the exact value of a register by name.
+    /// This is slower than `set_exact_register` and is intended for tests and debugging
+    pub fn set_exact<T>(&mut self, register: &T, value: BigInt)
+        where T: AsRef<str> {
+        let slot = self.table.slot(register.as_ref());
+        self.set_exact_register(slot, value);
+    }
+
+    /// Sets the exact value of the register in `slot`, even if it doesn't fit in an i64
+    pub fn set_exact_register(&mut self, slot: usize, value: BigInt) {
+        self.set_register(slot, value.saturate());
+        if value.to_i64().is_none() {
+            self.wide.insert(slot, value);
+        }
+    }
+
+    /// Checks whether any register has outgrown an i64
+    pub fn has_wide_registers(&self) -> bool {
+        !self.wide.is_empty()
+    }
+
+    /// Checks whether the register in `slot` has outgrown an i64
+    pub fn is_wide(&self, slot: usize) -> bool {
+        !self.wide.is_empty() && self.wide.contains_key(&slot)
+    }
+
+    /// Applies `operator` to the register in `slot` and `operand`, storing the result in the register
+    ///  and returning it.
+    /// If the arithmetic fails, the register is left unchanged and the error is kept until `take_error`
+    pub fn calculate(&mut self, operator: Operator, slot: usize, operand: &Value) -> Option<i64> {
+        let wide = self.is_wide(slot) || match operand {
+            &Value::Register(other) => self.is_wide(other),
+            _ => false
+        };
+        if !wide {
+            match self.arithmetic.apply(operator, self.get_register(slot), self.get_value(operand)) {
+                Ok(result) => {
+                    self.set_register(slot, result);
+                    return Some(result);
+                },
+                // Unbounded arithmetic continues with a BigInt
+                Err(ArithmeticError::Overflow) if self.arithmetic == Arithmetic::Unbounded => {},
+                Err(error) => {
+                    self.error = Some(error);
+                    return None;
+                }
+            }
+        }
+        match self.get_exact_register(slot).apply(operator, &self.get_exact_value(operand)) {
+            Some(result) => {
+                self.set_exact_register(slot, result);
+                Some(self.get_register(slot))
+            },
+            None => {
+                self.error = Some(ArithmeticError::DivisionByZero);
+                None
+            }
+        }
+    }
+
+    /// Checks whether an arithmetic or device error is waiting to be taken with `take_fault`
+    pub fn has_fault(&self) -> bool {
+        self.error.is_some() || self.device_error.is_some()
+    }
+
+    /// Takes the error from the last arithmetic or device that failed, if any, as a fault of the
+    ///  instruction at `pc`
+    pub fn take_fault(&mut self, pc: i64) -> Option<Fault> {
+        if let Some(error) = self.error.take() {
+            return Some(Fault::Arithmetic { pc, error });
+        }
+        self.device_error.take().map(|error| Fault::Device { pc, error })
+    }
+
+    /// Gets the name and value of every register, in slot order
+    pub fn registers(&self) -> Vec<(String, i64)> {
+        (0..self.table.len())
+            .map(|slot| (String::from(self.table.name(slot).unwrap_or("")), self.get_register(slot)))
+            .collect()
+    }
+
+    /// Gets the name and exact value of every register, in slot order
+    pub fn exact_registers(&self) -> Vec<(String, BigInt)> {
+        (0..self.table.len())
+            .map(|slot| (String::from(self.table.name(slot).unwrap_or("")), self.get_exact_register(slot)))
+            .collect()
+    }
+
+    /// Gets the current PC and the name and value of every register
+    pub fn get_state(&self) -> MachineState {
+        MachineState { pc: self.pc, registers: self.registers() }
+    }
+
+    /// Gets the raw register values, indexed by slot
+    pub fn register_values(&self) -> &[i64] {
+        &self.registers
+    }
+
+    /// Gets the table used to map register names to slots
+    pub fn get_register_table(&self) -> &RegisterTable {
+        &self.table
+    }
+
+    /// Gets the table used to map register names to slots, so that instructions using new registers can be parsed
+    pub fn get_register_table_mut(&mut self) -> &mut RegisterTable {
+        &mut self.table
     }
 
     /// Gets the current PC value (convenience method, the same could be accomplished with `get` and `SpecialRegister`)
-    pub fn get_pc(&mut self) -> i64 {
-        let pc = SpecialRegister::PC.get_name();
-        self.get(&pc)
+    pub fn get_pc(&self) -> i64 {
+        self.pc
+    }
+
+    /// Sets the current PC value (convenience method, the same could be accomplished with `set` and `SpecialRegister`)
+    pub fn set_pc(&mut self, pc: i64) {
+        self.pc = pc;
     }
 
     /// Increments the current PC value by 1 (convenience method, the same could be accomplished with `set` and `SpecialRegister`)
@@ -72,23 +329,60 @@ impl Environment {
     /// Increments the current PC value by an `offset` - 1
     /// This method assumes the pc has already stepped before being called and thus the PC
     ///  is off by 1 from the original value (i.e. the environment has a delay slot even though the instruction set does not)
-    ///  (convenience method, the same could be accomplished with `get` and `SpecialRegister`)
+    ///  (convenience method, the same could be accomplished with `get` and `SpecialRegister`).
+    /// A jump too far to represent leaves the PC at the smallest or largest i64, outside of any program
     pub fn jump_pc(&mut self, offset: i64) {
-        let pc = SpecialRegister::PC.get_name();
-        let old_value = self.get(&pc);
-        self.set(&pc, old_value + offset - 1);
+        self.pc = self.pc.saturating_add(offset).saturating_sub(1);
     }
 
-    /// Sends `value` to a linked environment.
-    /// Does nothign if no environment has been linked
+    /// Sends `value` to every linked environment and the output device.
+    /// Does nothing if no environment has been linked and no output device is attached.
+    /// If the output device fails, the error is kept until `take_fault`
     pub fn send(&mut self, value: i64) {
-        if let Some(ref link) = self.linked_env {
-            link.borrow_mut().rcv.push_back(value);
+        for link in self.links.iter() {
+            link.borrow_mut().enqueue(value);
+        }
+        if let Some(ref mut output) = self.devices.output {
+            if let Err(error) = output.write(value) {
+                self.device_error = Some(error);
+            }
         }
     }
 
-    /// Receives a value sent from a linked environment
+    /// Queues `value` to be received, as if a linked environment had sent it
+    pub fn enqueue(&mut self, value: i64) {
+        self.rcv.push_back(value);
+    }
+
+    /// Plays a sound, which is kept in the receive queue until the next sound is played
+    pub fn play(&mut self, frequency: i64) {
+        self.rcv.clear();
+        self.rcv.push_back(frequency);
+    }
+
+    /// Gets the frequency of the last sound played
+    pub fn last_played(&self) -> Option<i64> {
+        self.rcv.back().cloned()
+    }
+
+    /// Receives a value sent from a linked environment, or read from the input device if none is queued.
+    /// If the input device fails, the error is kept until `take_fault`
     pub fn receive(&mut self) -> Option<i64> {
-        return self.rcv.pop_front()
+        if let Some(value) = self.rcv.pop_front() {
+            return Some(value);
+        }
+        match self.devices.input.as_mut().map(|input| input.read()) {
+            Some(Ok(value)) => value,
+            Some(Err(error)) => {
+                self.device_error = Some(error);
+                None
+            },
+            None => None
+        }
+    }
+
+    /// Gets the values waiting to be received, oldest first
+    pub fn queue(&self) -> Vec<i64> {
+        self.rcv.iter().cloned().collect()
     }
 }
\ No newline at end of file
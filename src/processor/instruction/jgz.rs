@@ -1,6 +1,6 @@
 use super::{InstructionType, Instruction};
 use super::super::Environment;
-use super::super::Value;
+use super::super::{Value, RegisterTable};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Jgz {
@@ -13,10 +13,15 @@ impl Instruction for Jgz {
         InstructionType::Jgz
     }
 
+    fn get_operands(&self) -> Vec<Value> {
+        vec![self.condition, self.value]
+    }
+
     fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        // A register beyond an i64 reads as the smallest or largest i64, which has the same sign
         let reg_value = environment.get_value(&self.condition);
         if reg_value > 0 {
-            let offset = environment.get_value(&self.value);
+            let offset = environment.get_narrow_value(&self.value)?;
             environment.jump_pc(offset);
             return Some(offset);
         }
@@ -24,9 +29,9 @@ impl Instruction for Jgz {
     }
 }
 
-pub fn parse(reg: &str, val: &str) -> Box<Jgz> {
-    let condition = Value::parse(reg);
-    let value = Value::parse(val);
+pub fn parse(reg: &str, val: &str, registers: &mut RegisterTable) -> Box<Jgz> {
+    let condition = Value::parse(reg, registers);
+    let value = Value::parse(val, registers);
     return Box::new(Jgz {condition, value});
 }
 
@@ -37,21 +42,24 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let mut registers = RegisterTable::new();
         let reg = "a";
         let value = "3";
-        assert_eq!(parse(reg, value), Box::new(Jgz {condition: Value::Register("a".into()), value:  Value::Literal(3)}));
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Jgz {condition: Value::Register(0), value:  Value::Literal(3)}));
 
         let reg = "a";
         let value = "b";
-        assert_eq!(parse(reg, value), Box::new(Jgz {condition: Value::Register("a".into()), value:  Value::Register("b".into())}));
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Jgz {condition: Value::Register(0), value:  Value::Register(1)}));
     }
 
     #[test]
     fn test_execute() {
-        let mut environment = Environment::new();
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
         // The environment assumes PC has been updated before running the current instruction
         environment.step_pc();
-        let instruction = Jgz { condition: Value::Register("a".into()), value: Value::Literal(2) };
+        let instruction = Jgz { condition: Value::Register(a), value: Value::Literal(2) };
         instruction.execute(&mut environment);
         assert_eq!(environment.get(&"a"), 0);
         assert_eq!(environment.get(&SpecialRegister::PC.get_name()),  1);
@@ -64,10 +72,12 @@ mod test {
 
     #[test]
     fn test_execute_register() {
-        let mut environment = Environment::new();
+        let mut registers = RegisterTable::new();
+        let (a, b) = (registers.slot("a"), registers.slot("b"));
+        let mut environment = Environment::with_registers(registers);
         // The environment assumes PC has been updated before running the current instruction
         environment.step_pc();
-        let instruction = Jgz { condition: Value::Register("a".into()), value: Value::Register("b".into()) };
+        let instruction = Jgz { condition: Value::Register(a), value: Value::Register(b) };
         instruction.execute(&mut environment);
         assert_eq!(environment.get(&"a"), 0);
         assert_eq!(environment.get(&"b"), 0);
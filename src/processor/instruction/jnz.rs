@@ -1,4 +1,4 @@
-use super::{InstructionType, Instruction, Environment, Value};
+use super::{InstructionType, Instruction, Environment, Value, RegisterTable};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Jnz {
@@ -11,19 +11,25 @@ impl Instruction for Jnz {
         InstructionType::Jnz
     }
 
+    fn get_operands(&self) -> Vec<Value> {
+        vec![self.condition, self.value]
+    }
+
     fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        // A register beyond an i64 reads as the smallest or largest i64, which is just as non-zero
         let condition = environment.get_value(&self.condition);
         if condition != 0 {
-            let offset = environment.get_value(&self.value);
+            let offset = environment.get_narrow_value(&self.value)?;
             environment.jump_pc(offset);
+            return Some(offset);
         }
         None
     }
 }
 
-pub fn parse(cond: &str, val: &str) -> Box<Jnz> {
-    let value = Value::parse(val);
-    let condition = Value::parse(cond);
+pub fn parse(cond: &str, val: &str, registers: &mut RegisterTable) -> Box<Jnz> {
+    let value = Value::parse(val, registers);
+    let condition = Value::parse(cond, registers);
     return Box::new(Jnz {condition, value});
 }
 
@@ -34,21 +40,24 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let mut registers = RegisterTable::new();
         let reg = "a";
         let value = "3";
-        assert_eq!(parse(reg, value), Box::new(Jnz {condition: Value::Register(reg.into()), value:  Value::Literal(3)}));
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Jnz {condition: Value::Register(0), value:  Value::Literal(3)}));
 
         let reg = "a";
         let value = "b";
-        assert_eq!(parse(reg, value), Box::new(Jnz {condition: Value::Register(reg.into()), value:  Value::Register("b".into())}));
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Jnz {condition: Value::Register(0), value:  Value::Register(1)}));
     }
 
     #[test]
     fn test_execute() {
-        let mut environment = Environment::new();
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
         // The environment assumes PC has been updated before running the current instruction
         environment.step_pc();
-        let instruction = Jnz { condition: Value::Register("a".into()), value: Value::Literal(2) };
+        let instruction = Jnz { condition: Value::Register(a), value: Value::Literal(2) };
         instruction.execute(&mut environment);
         assert_eq!(environment.get(&"a"), 0);
         assert_eq!(environment.get(&SpecialRegister::PC.get_name()),  1);
@@ -61,10 +70,12 @@ mod test {
 
     #[test]
     fn test_execute_register() {
-        let mut environment = Environment::new();
+        let mut registers = RegisterTable::new();
+        let (a, b) = (registers.slot("a"), registers.slot("b"));
+        let mut environment = Environment::with_registers(registers);
         // The environment assumes PC has been updated before running the current instruction
         environment.step_pc();
-        let instruction = Jnz { condition: Value::Register("a".into()), value: Value::Register("b".into()) };
+        let instruction = Jnz { condition: Value::Register(a), value: Value::Register(b) };
         instruction.execute(&mut environment);
         assert_eq!(environment.get(&"a"), 0);
         assert_eq!(environment.get(&"b"), 0);
@@ -0,0 +1,325 @@
+use super::{Environment, Value, RegisterTable, SpecialRegister};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::error::Error;
+use std::collections::HashMap;
+
+mod snd;
+mod set;
+mod add;
+mod sub;
+mod mul;
+mod rem;
+mod rcv;
+mod jgz;
+mod jnz;
+mod play;
+mod recover;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum InstructionType {
+    Halt,
+    Snd,
+    Set,
+    Add,
+    Sub,
+    Mul,
+    Mod,
+    Rcv,
+    Jgz,
+    Jnz,
+    /// `snd` when it plays a sound rather than sending a value
+    Play,
+    /// `rcv` when it recovers a sound rather than receiving a value
+    Recover
+}
+
+impl InstructionType {
+    /// Gets the mnemonic used for the instruction in a program
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            InstructionType::Halt => "halt",
+            InstructionType::Snd => "snd",
+            InstructionType::Set => "set",
+            InstructionType::Add => "add",
+            InstructionType::Sub => "sub",
+            InstructionType::Mul => "mul",
+            InstructionType::Mod => "mod",
+            InstructionType::Rcv => "rcv",
+            InstructionType::Jgz => "jgz",
+            InstructionType::Jnz => "jnz",
+            InstructionType::Play => "snd",
+            InstructionType::Recover => "rcv",
+        }
+    }
+}
+
+/// An instruction in the instruction set
+/// Instructions are immutable once parsed, so a program can be shared between threads
+pub trait Instruction: Debug + Send + Sync {
+    fn get_type(&self) -> InstructionType;
+    /// The operands of the instruction in the order they appear in a program.
+    /// Registers that the instruction writes to are returned as `Value::Register`
+    fn get_operands(&self) -> Vec<Value>;
+    fn execute(&self, &mut Environment) -> Option<i64>;
+}
+
+/// Formats an instruction as it would appear in a program
+pub fn disassemble(instruction: &Instruction, registers: &RegisterTable) -> String {
+    let mut parts = vec![instruction.get_type().get_name().to_string()];
+    parts.extend(instruction.get_operands().iter().map(|operand| operand.format(registers)));
+    parts.join(" ")
+}
+
+/// The kind of operand an instruction expects
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Operand {
+    /// A register the instruction writes to
+    Register,
+    /// A literal or a register
+    Value,
+}
+
+impl InstructionType {
+    fn operands(&self) -> &'static [Operand] {
+        match *self {
+            InstructionType::Halt => &[],
+            InstructionType::Snd | InstructionType::Play | InstructionType::Recover => &[Operand::Value],
+            InstructionType::Rcv => &[Operand::Register],
+            InstructionType::Jgz | InstructionType::Jnz => &[Operand::Value, Operand::Value],
+            _ => &[Operand::Register, Operand::Value],
+        }
+    }
+}
+
+/// Why an instruction couldn't be parsed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Reason {
+    /// The opcode isn't part of the instruction set being parsed
+    UnknownOpcode(String),
+    WrongOperandCount { expected: usize, found: usize },
+    /// An operand looks like a number but doesn't fit in an i64
+    BadLiteral(String),
+    /// A number was given where the instruction writes to a register
+    ExpectedRegister(String),
+    /// A label is empty or defined more than once
+    BadLabel(String),
+    /// A special register, like `pc`, was used as an ordinary register
+    ReservedRegister(String),
+    /// A name is used both as a label and as a register, so a jump to it could mean either
+    AmbiguousLabel(String),
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Reason::UnknownOpcode(ref opcode) => write!(f, "unknown opcode `{}`", opcode),
+            Reason::WrongOperandCount { expected, found } => write!(f, "expected {} operands, found {}", expected, found),
+            Reason::BadLiteral(ref literal) => write!(f, "`{}` is not a valid number", literal),
+            Reason::ExpectedRegister(ref operand) => write!(f, "expected a register, found `{}`", operand),
+            Reason::BadLabel(ref label) => write!(f, "label `{}` is empty or already defined", label),
+            Reason::ReservedRegister(ref register) => write!(f, "`{}` is reserved and can't be used as a register", register),
+            Reason::AmbiguousLabel(ref label) => write!(f, "`{}` is both a label and a register", label),
+        }
+    }
+}
+
+/// An error on a line of a program. `line` counts from 1 and `text` is the line as written
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError<'a> {
+    pub line: usize,
+    pub text: &'a str,
+    pub reason: Reason,
+}
+
+impl <'a> Display for ParseError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {} in `{}`", self.line, self.reason, self.text)
+    }
+}
+
+impl <'a> Error for ParseError<'a> {
+    fn description(&self) -> &str {
+        match self.reason {
+            Reason::UnknownOpcode(_) => "Unknown opcode",
+            Reason::WrongOperandCount { .. } => "Wrong number of operands",
+            Reason::BadLiteral(_) => "Bad literal",
+            Reason::ExpectedRegister(_) => "Expected a register",
+            Reason::BadLabel(_) => "Bad label",
+            Reason::ReservedRegister(_) => "Reserved register",
+            Reason::AmbiguousLabel(_) => "Ambiguous label",
+        }
+    }
+}
+
+/// Checks whether an operand is meant to be a number rather than a register name
+fn is_numeric(operand: &str) -> bool {
+    operand.starts_with(|c: char| c.is_digit(10) || c == '-' || c == '+')
+}
+
+/// Parses a single instruction, allocating any registers it names in `registers`.
+/// The instruction set decides what a mnemonic means, e.g. whether `snd` sends a value or plays a sound.
+/// Fails if the instruction is not part of `instruction_set` or has bad operands
+pub fn parse(input: &str, instruction_set: &[InstructionType], registers: &mut RegisterTable) -> Result<Box<Instruction>, Reason> {
+    let parts = input.split_whitespace().collect::<Vec<&str>>();
+    let opcode = parts.first().cloned().unwrap_or("");
+    let instruction_type = match instruction_set.iter().find(|instruction_type| instruction_type.get_name() == opcode) {
+        Some(&instruction_type) if instruction_type != InstructionType::Halt => instruction_type,
+        _ => return Err(Reason::UnknownOpcode(String::from(opcode)))
+    };
+    let operands = instruction_type.operands();
+    if parts.len() - 1 != operands.len() {
+        return Err(Reason::WrongOperandCount { expected: operands.len(), found: parts.len() - 1 });
+    }
+    for (&part, &operand) in parts[1..].iter().zip(operands.iter()) {
+        match operand {
+            Operand::Register if is_numeric(part) => return Err(Reason::ExpectedRegister(String::from(part))),
+            Operand::Value if is_numeric(part) && part.parse::<i64>().is_err() => return Err(Reason::BadLiteral(String::from(part))),
+            // The environment always reads and writes `pc` as the program counter
+            _ if part == SpecialRegister::PC.get_name() => return Err(Reason::ReservedRegister(String::from(part))),
+            _ => {}
+        }
+    }
+    Ok(match instruction_type {
+        InstructionType::Snd => snd::parse(parts[1], registers),
+        InstructionType::Set => set::parse(parts[1], parts[2], registers),
+        InstructionType::Add => add::parse(parts[1], parts[2], registers),
+        InstructionType::Sub => sub::parse(parts[1], parts[2], registers),
+        InstructionType::Mul => mul::parse(parts[1], parts[2], registers),
+        InstructionType::Mod => rem::parse(parts[1], parts[2], registers),
+        InstructionType::Rcv => rcv::parse(parts[1], registers),
+        InstructionType::Jgz => jgz::parse(parts[1], parts[2], registers),
+        InstructionType::Jnz => jnz::parse(parts[1], parts[2], registers),
+        InstructionType::Play => play::parse(parts[1], registers),
+        InstructionType::Recover => recover::parse(parts[1], registers),
+        InstructionType::Halt => return Err(Reason::UnknownOpcode(String::from(opcode)))
+    })
+}
+
+/// Parses a program of newline separated instructions.
+///
+/// In addition to raw instructions, a program may contain
+/// * blank lines
+/// * comments, starting with `#` and running to the end of the line
+/// * labels, e.g. `loop:`, either on their own line or before an instruction
+///
+/// The offset of a `jgz`/`jnz` may name a label, in which case it is resolved to the
+///  relative offset of the labelled instruction so the program executes exactly as if
+///  the offset had been written by hand. A label can't share its name with a register.
+/// Registers are lowered to slots in `registers`.
+/// Fails on the first line that cannot be parsed
+pub fn parse_program<'a>(input: &'a str, instruction_set: &[InstructionType], registers: &mut RegisterTable) -> Result<Vec<Box<Instruction>>, ParseError<'a>> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    for (number, text) in input.split("\n").enumerate() {
+        // Drop comments
+        let line = text.split("#").next().unwrap_or("");
+        let mut parts = line.split_whitespace().collect::<Vec<&str>>();
+        // Strip the label (if any) and remember which instruction it points to
+        if parts.len() > 0 && parts[0].ends_with(":") {
+            let label = parts.remove(0).trim_right_matches(":");
+            if label.is_empty() || labels.insert(label, lines.len() as i64).is_some() {
+                return Err(ParseError { line: number + 1, text: text.trim(), reason: Reason::BadLabel(String::from(label)) });
+            }
+            // Registers given up front, like a program's ID
+            if registers.find(label).is_some() {
+                return Err(ParseError { line: number + 1, text: text.trim(), reason: Reason::AmbiguousLabel(String::from(label)) });
+            }
+        }
+        if parts.len() > 0 {
+            lines.push((number + 1, text.trim(), parts));
+        }
+    }
+
+    // Every operand other than a jump offset names a register unless it is a number
+    for &(line, text, ref parts) in lines.iter() {
+        let jump = parts[0] == "jgz" || parts[0] == "jnz";
+        let names = parts.iter().enumerate().skip(1).filter(|&(i, _)| !(jump && i == 2)).map(|(_, &operand)| operand);
+        if let Some(clash) = names.filter(|&operand| !is_numeric(operand)).find(|&operand| labels.contains_key(operand)) {
+            return Err(ParseError { line, text, reason: Reason::AmbiguousLabel(String::from(clash)) });
+        }
+    }
+
+    let mut instructions = Vec::new();
+    for (pc, (line, text, mut parts)) in lines.into_iter().enumerate() {
+        // Resolve jump targets that name a label into a relative offset
+        let offset = match (parts[0], parts.get(2).and_then(|target| labels.get(target))) {
+            ("jgz", Some(target)) | ("jnz", Some(target)) => Some((target - pc as i64).to_string()),
+            _ => None
+        };
+        if let Some(ref offset) = offset {
+            parts[2] = offset;
+        }
+        let instruction = parse(&parts.join(" "), instruction_set, registers).map_err(|reason| ParseError { line, text, reason })?;
+        instructions.push(instruction);
+    }
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_instruction_set() {
+        let instruction_set = [InstructionType::Set, InstructionType::Jnz];
+        let mut registers = RegisterTable::new();
+        assert_eq!(parse("set a 1", &instruction_set, &mut registers).map(|i| i.get_type()), Ok(InstructionType::Set));
+        assert_eq!(parse("jnz a 2", &instruction_set, &mut registers).map(|i| i.get_type()), Ok(InstructionType::Jnz));
+        assert_eq!(parse("jgz a 2", &instruction_set, &mut registers).unwrap_err(), Reason::UnknownOpcode(String::from("jgz")));
+        assert_eq!(parse("nop a 2", &instruction_set, &mut registers).unwrap_err(), Reason::UnknownOpcode(String::from("nop")));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let instruction_set = [InstructionType::Set, InstructionType::Jnz];
+        let mut registers = RegisterTable::new();
+        for line in ["set a -3", "set b a", "jnz 1 b"].iter() {
+            let instruction = parse(line, &instruction_set, &mut registers).unwrap();
+            assert_eq!(&disassemble(&*instruction, &registers), line);
+        }
+    }
+
+    #[test]
+    fn test_parse_program_labels_and_comments() {
+        let instruction_set = [InstructionType::Set, InstructionType::Sub, InstructionType::Jnz];
+        let input = "# count down from 3\nset a 3\n\nloop: sub a 1 # decrement\njnz a loop\njnz 1 end\nset a 5\nend:\nset b 1";
+        let labelled = parse_program(input, &instruction_set, &mut RegisterTable::new()).unwrap();
+        let raw = parse_program("set a 3\nsub a 1\njnz a -1\njnz 1 2\nset a 5\nset b 1", &instruction_set, &mut RegisterTable::new()).unwrap();
+        assert_eq!(format!("{:?}", labelled), format!("{:?}", raw));
+    }
+
+    #[test]
+    fn test_parse_program_duplicate_label() {
+        let instruction_set = [InstructionType::Set];
+        let error = parse_program("start:\nset a 1\nstart:\nset a 2", &instruction_set, &mut RegisterTable::new()).unwrap_err();
+        assert_eq!(error, ParseError { line: 3, text: "start:", reason: Reason::BadLabel(String::from("start")) });
+    }
+
+    #[test]
+    fn test_parse_program_ambiguous_label() {
+        let instruction_set = [InstructionType::Set, InstructionType::Jnz];
+        let parse_error = |input, registers: &mut RegisterTable| parse_program(input, &instruction_set, registers).unwrap_err();
+        let error = parse_error("loop: set a 1\njnz a loop\nset loop 2", &mut RegisterTable::new());
+        assert_eq!(error, ParseError { line: 3, text: "set loop 2", reason: Reason::AmbiguousLabel(String::from("loop")) });
+        assert_eq!(parse_error("jnz loop 1\nloop: set a 1", &mut RegisterTable::new()).reason, Reason::AmbiguousLabel(String::from("loop")));
+        // A register the program starts with can't be a label either
+        let mut registers = RegisterTable::new();
+        registers.slot("p");
+        assert_eq!(parse_error("p: jnz 1 p", &mut registers).to_string(), "line 1: `p` is both a label and a register in `p: jnz 1 p`");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let instruction_set = [InstructionType::Set, InstructionType::Rcv, InstructionType::Jnz];
+        let parse_error = |input| parse_program(input, &instruction_set, &mut RegisterTable::new()).unwrap_err();
+        assert_eq!(parse_error("set a 1\n\nset a").to_string(), "line 3: expected 2 operands, found 1 in `set a`");
+        assert_eq!(parse_error("rcv").reason, Reason::WrongOperandCount { expected: 1, found: 0 });
+        assert_eq!(parse_error("set a 1 2").reason, Reason::WrongOperandCount { expected: 2, found: 3 });
+        assert_eq!(parse_error("# comment\nsnd a").to_string(), "line 2: unknown opcode `snd` in `snd a`");
+        assert_eq!(parse_error("set a 12x").reason, Reason::BadLiteral(String::from("12x")));
+        assert_eq!(parse_error("jnz 1 99999999999999999999").reason, Reason::BadLiteral(String::from("99999999999999999999")));
+        assert_eq!(parse_error("set 1 a").reason, Reason::ExpectedRegister(String::from("1")));
+        assert_eq!(parse_error("set pc 5").reason, Reason::ReservedRegister(String::from("pc")));
+        assert_eq!(parse_error("jnz 1 pc").reason, Reason::ReservedRegister(String::from("pc")));
+    }
+}
@@ -0,0 +1,45 @@
+use super::{InstructionType, Instruction};
+use super::super::{Environment, Value, RegisterTable};
+
+/// `snd` as a sound card understands it: plays a sound with a frequency
+#[derive(Debug, PartialEq, Eq)]
+pub struct Play {
+    frequency: Value
+}
+
+impl Instruction for Play {
+    fn get_type(&self) -> InstructionType {
+        InstructionType::Play
+    }
+
+    fn get_operands(&self) -> Vec<Value> {
+        vec![self.frequency]
+    }
+
+    fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        let frequency = environment.get_narrow_value(&self.frequency)?;
+        environment.play(frequency);
+        Some(frequency)
+    }
+}
+
+pub fn parse(input: &str, registers: &mut RegisterTable) -> Box<Play> {
+    let frequency = Value::parse(input, registers);
+    return Box::new(Play {frequency});
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
+        environment.set(&"a", 4);
+        assert_eq!(Play { frequency: Value::Register(a) }.execute(&mut environment), Some(4));
+        assert_eq!(Play { frequency: Value::Literal(7) }.execute(&mut environment), Some(7));
+        assert_eq!(environment.last_played(), Some(7));
+    }
+}
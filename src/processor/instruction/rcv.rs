@@ -1,9 +1,9 @@
 use super::{InstructionType, Instruction};
-use super::super::{Environment};
+use super::super::{Environment, Value, RegisterTable};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Rcv {
-    register: String
+    register: usize
 }
 
 impl Instruction for Rcv {
@@ -11,11 +11,15 @@ impl Instruction for Rcv {
         InstructionType::Rcv
     }
 
+    fn get_operands(&self) -> Vec<Value> {
+        vec![Value::Register(self.register)]
+    }
+
     fn execute(&self, environment: &mut Environment) -> Option<i64> {
         let received = environment.receive();
         match received {
             Some(value) => {
-                environment.set(&self.register, value);
+                environment.set_register(self.register, value);
                 Some(value)
             },
             None => {
@@ -26,8 +30,8 @@ impl Instruction for Rcv {
     }
 }
 
-pub fn parse(input: &str) -> Box<Rcv> {
-    return Box::new(Rcv {register: input.into()});
+pub fn parse(input: &str, registers: &mut RegisterTable) -> Box<Rcv> {
+    return Box::new(Rcv {register: registers.slot(input)});
 }
 
 #[cfg(test)]
@@ -38,7 +42,8 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let mut registers = RegisterTable::new();
         let reg = "a";
-        assert_eq!(parse(reg), Box::new(Rcv {register: "a".into()}));
+        assert_eq!(parse(reg, &mut registers), Box::new(Rcv {register: 0}));
     }
 }
\ No newline at end of file
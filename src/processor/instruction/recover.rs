@@ -0,0 +1,50 @@
+use super::{InstructionType, Instruction};
+use super::super::{Environment, Value, RegisterTable};
+
+/// `rcv` as a sound card understands it: recovers the frequency of the last sound played,
+///  but only if the condition isn't zero
+#[derive(Debug, PartialEq, Eq)]
+pub struct Recover {
+    condition: Value
+}
+
+impl Instruction for Recover {
+    fn get_type(&self) -> InstructionType {
+        InstructionType::Recover
+    }
+
+    fn get_operands(&self) -> Vec<Value> {
+        vec![self.condition]
+    }
+
+    fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        if environment.get_value(&self.condition) != 0 {
+            return environment.last_played();
+        }
+        None
+    }
+}
+
+pub fn parse(input: &str, registers: &mut RegisterTable) -> Box<Recover> {
+    let condition = Value::parse(input, registers);
+    return Box::new(Recover {condition});
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
+        let instruction = Recover { condition: Value::Register(a) };
+        environment.play(4);
+        assert_eq!(instruction.execute(&mut environment), None);
+        environment.set(&"a", 1);
+        assert_eq!(instruction.execute(&mut environment), Some(4));
+        // Recovering doesn't forget the sound
+        assert_eq!(instruction.execute(&mut environment), Some(4));
+    }
+}
@@ -0,0 +1,77 @@
+use super::{InstructionType, Instruction};
+use super::super::Environment;
+use super::super::arithmetic::Operator;
+use super::super::{Value, RegisterTable};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Rem {
+    register: usize,
+    value: Value
+}
+
+impl Instruction for Rem {
+    fn get_type(&self) -> InstructionType {
+        InstructionType::Mod
+    }
+
+    fn get_operands(&self) -> Vec<Value> {
+        vec![Value::Register(self.register), self.value]
+    }
+
+    fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        environment.calculate(Operator::Mod, self.register, &self.value)
+    }
+}
+
+pub fn parse(reg: &str, val: &str, registers: &mut RegisterTable) -> Box<Rem> {
+    let register = registers.slot(reg);
+    let value = Value::parse(val, registers);
+    return Box::new(Rem {register, value});
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut registers = RegisterTable::new();
+        let reg = "a";
+        let value = "3";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Rem {register: 0, value:  Value::Literal(3)}));
+
+        let reg = "a";
+        let value = "-3";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Rem {register: 0, value:  Value::Literal(-3)}));
+
+        let reg = "a";
+        let value = "b";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Rem {register: 0, value:  Value::Register(1)}));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
+        environment.set(&"a", 8);
+        let instruction = Rem {register: a, value:  Value::Literal(3)};
+        instruction.execute(&mut environment);
+        assert_eq!(environment.get(&"a"), 2);
+        instruction.execute(&mut environment);
+        assert_eq!(environment.get(&"a"), 2);
+    }
+
+    #[test]
+    fn test_execute_register() {
+        let mut registers = RegisterTable::new();
+        let (a, b) = (registers.slot("a"), registers.slot("b"));
+        let mut environment = Environment::with_registers(registers);
+        environment.set(&"a", 8);
+        environment.set(&"b", 3);
+        let instruction = Rem {register: a, value: Value::Register(b)};
+        instruction.execute(&mut environment);
+        assert_eq!(environment.get(&"a"), 2);
+        assert_eq!(environment.get(&"b"), 3);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+use super::{InstructionType, Instruction};
+use super::super::Environment;
+use super::super::{Value, RegisterTable};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Set {
+    register: usize,
+    value: Value
+}
+
+impl Instruction for Set {
+    fn get_type(&self) -> InstructionType {
+        InstructionType::Set
+    }
+
+    fn get_operands(&self) -> Vec<Value> {
+        vec![Value::Register(self.register), self.value]
+    }
+
+    fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        match self.value {
+            Value::Register(slot) if environment.is_wide(slot) => {
+                let value = environment.get_exact_register(slot);
+                environment.set_exact_register(self.register, value);
+            },
+            _ => {
+                let value = environment.get_value(&self.value);
+                environment.set_register(self.register, value);
+            }
+        }
+        Some(environment.get_register(self.register))
+    }
+}
+
+pub fn parse(reg: &str, val: &str, registers: &mut RegisterTable) -> Box<Set> {
+    let register = registers.slot(reg);
+    let value = Value::parse(val, registers);
+    return Box::new(Set {register, value});
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut registers = RegisterTable::new();
+        let reg = "a";
+        let value = "3";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Set {register: 0, value:  Value::Literal(3)}));
+
+        let reg = "a";
+        let value = "-3";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Set {register: 0, value:  Value::Literal(-3)}));
+
+        let reg = "a";
+        let value = "b";
+        assert_eq!(parse(reg, value, &mut registers), Box::new(Set {register: 0, value:  Value::Register(1)}));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let mut environment = Environment::with_registers(registers);
+        let instruction = Set {register: a, value:  Value::Literal(5)};
+        instruction.execute(&mut environment);
+        assert_eq!(environment.get(&"a"), 5);
+    }
+
+    #[test]
+    fn test_execute_register() {
+        let mut registers = RegisterTable::new();
+        let (a, b) = (registers.slot("a"), registers.slot("b"));
+        let mut environment = Environment::with_registers(registers);
+        environment.set(&"a", 2);
+        environment.set(&"b", 3);
+        let instruction = Set {register: a, value: Value::Register(b)};
+        instruction.execute(&mut environment);
+        assert_eq!(environment.get(&"a"), 3);
+        assert_eq!(environment.get(&"b"), 3);
+    }
+
+    #[test]
+    fn test_execute_wide() {
+        let mut registers = RegisterTable::new();
+        let (a, b) = (registers.slot("a"), registers.slot("b"));
+        let mut environment = Environment::with_registers(registers);
+        environment.set_exact_register(b, "99999999999999999999".parse().unwrap());
+        Set {register: a, value: Value::Register(b)}.execute(&mut environment);
+        assert_eq!(environment.get_exact_register(a).to_string(), "99999999999999999999");
+
+        // Setting a narrow value leaves nothing wide behind
+        Set {register: a, value: Value::Literal(1)}.execute(&mut environment);
+        Set {register: b, value: Value::Register(a)}.execute(&mut environment);
+        assert!(!environment.has_wide_registers());
+    }
+}
@@ -1,5 +1,5 @@
 use super::{InstructionType, Instruction};
-use super::super::{Environment, Value};
+use super::super::{Environment, Value, RegisterTable};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Snd {
@@ -11,15 +11,19 @@ impl Instruction for Snd {
         InstructionType::Snd
     }
 
+    fn get_operands(&self) -> Vec<Value> {
+        vec![self.sound]
+    }
+
     fn execute(&self, environment: &mut Environment) -> Option<i64> {
-        let value = environment.get_value(&self.sound);
+        let value = environment.get_narrow_value(&self.sound)?;
         environment.send(value);
         Some(value)
     }
 }
 
-pub fn parse(input: &str) -> Box<Snd> {
-    let sound = Value::parse(input);
+pub fn parse(input: &str, registers: &mut RegisterTable) -> Box<Snd> {
+    let sound = Value::parse(input, registers);
     return Box::new(Snd {sound});
 }
 
@@ -29,7 +33,8 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let mut registers = RegisterTable::new();
         let reg = "12";
-        assert_eq!(parse(reg), Box::new(Snd {sound: Value::Literal(12)}));
+        assert_eq!(parse(reg, &mut registers), Box::new(Snd {sound: Value::Literal(12)}));
     }
 }
\ No newline at end of file
@@ -0,0 +1,212 @@
+use super::{RegisterTable, Value};
+use super::instruction::{Instruction, InstructionType};
+use super::cfg::{ControlFlowGraph, Successor};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// A likely mistake found in a program without running it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Warning {
+    /// A register is read on some path before anything writes to it, so it is silently 0
+    UninitializedRead { pc: usize, register: String },
+    /// No path from the start of the program reaches the instructions in [start, end)
+    Unreachable { start: usize, end: usize },
+    /// A jump that is always taken and leaves the program
+    LeavesProgram { pc: usize, target: i64 },
+    /// A `rcv` in a program that never sends anything, so it waits forever
+    UnsatisfiableRcv { pc: usize },
+}
+
+impl Warning {
+    /// The PC the warning refers to
+    pub fn pc(&self) -> usize {
+        match *self {
+            Warning::UninitializedRead { pc, .. } | Warning::LeavesProgram { pc, .. } | Warning::UnsatisfiableRcv { pc } => pc,
+            Warning::Unreachable { start, .. } => start,
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Warning::UninitializedRead { pc, ref register } => write!(f, "{}: register `{}` may be read before it is written, it will be 0", pc, register),
+            Warning::Unreachable { start, end } if end == start + 1 => write!(f, "{}: instruction is unreachable", start),
+            Warning::Unreachable { start, end } => write!(f, "{}..{}: instructions are unreachable", start, end),
+            Warning::LeavesProgram { pc, target } => write!(f, "{}: jump to {} leaves the program", pc, target),
+            Warning::UnsatisfiableRcv { pc } => write!(f, "{}: rcv can never be satisfied, nothing is ever sent", pc),
+        }
+    }
+}
+
+/// Gets the registers an instruction reads
+fn reads(instruction: &Instruction) -> Vec<usize> {
+    let operands = instruction.get_operands();
+    // These overwrite their first operand without reading it
+    let skip = match instruction.get_type() {
+        InstructionType::Set | InstructionType::Rcv => 1,
+        _ => 0
+    };
+    operands.into_iter().skip(skip).filter_map(|operand| match operand {
+        Value::Register(register) => Some(register),
+        _ => None
+    }).collect()
+}
+
+/// Gets the register an instruction writes, if any
+fn writes(instruction: &Instruction) -> Option<usize> {
+    match (instruction.get_type(), instruction.get_operands().first()) {
+        (InstructionType::Snd, _) | (InstructionType::Jgz, _) | (InstructionType::Jnz, _) => None,
+        (InstructionType::Play, _) | (InstructionType::Recover, _) => None,
+        (_, Some(&Value::Register(register))) => Some(register),
+        _ => None
+    }
+}
+
+/// Analyses a program, returning warnings ordered by PC.
+///
+/// `initialized` names the registers that are set before the program starts (e.g. `p` in a duet).
+/// Jumps through a register could go anywhere, so if one is reachable nothing is reported as unreachable.
+/// The targets of such jumps are not followed when looking for uninitialized reads
+pub fn lint(instructions: &[Box<Instruction>], registers: &RegisterTable, initialized: &[&str]) -> Vec<Warning> {
+    let graph = ControlFlowGraph::new(instructions);
+    let blocks = graph.blocks();
+    let index = |pc: usize| blocks.iter().position(|block| block.start == pc);
+    let successors = |block: usize| blocks[block].taken.iter().chain(blocks[block].next.iter()).filter_map(|successor| match *successor {
+        Successor::Pc(pc) => index(pc),
+        _ => None
+    }).collect::<Vec<usize>>();
+    let mut warnings = Vec::new();
+
+    // Find the reachable blocks
+    let mut reachable = vec![false; blocks.len()];
+    let mut pending = if blocks.is_empty() { Vec::new() } else { vec![0] };
+    while let Some(block) = pending.pop() {
+        if !reachable[block] {
+            reachable[block] = true;
+            pending.extend(successors(block));
+        }
+    }
+    let dynamic = blocks.iter().zip(reachable.iter()).any(|(block, &reachable)| reachable && block.taken == Some(Successor::Dynamic));
+    if !dynamic {
+        let mut unreachable = blocks.iter().zip(reachable.iter()).filter(|&(_, &reachable)| !reachable).map(|(block, _)| (block.start, block.end)).collect::<Vec<(usize, usize)>>();
+        // Merge neighbouring blocks into one warning
+        unreachable.dedup_by(|next, previous| {
+            if previous.1 == next.0 {
+                previous.1 = next.1;
+                return true;
+            }
+            false
+        });
+        warnings.extend(unreachable.into_iter().map(|(start, end)| Warning::Unreachable { start, end }));
+    }
+
+    // Find the registers that are written on every path to the start of each block.
+    // None stands for every register, the starting point for blocks that haven't been reached yet
+    let mut written: Vec<Option<HashSet<usize>>> = vec![None; blocks.len()];
+    if !blocks.is_empty() {
+        written[0] = Some(initialized.iter().filter_map(|name| registers.find(name)).collect());
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in (0..blocks.len()).filter(|&block| reachable[block]) {
+            let mut out = match written[block] {
+                Some(ref written) => written.clone(),
+                None => continue
+            };
+            out.extend(instructions[blocks[block].start..blocks[block].end].iter().filter_map(|instruction| writes(&**instruction)));
+            for successor in successors(block) {
+                let merged = match written[successor] {
+                    Some(ref written) => written.intersection(&out).cloned().collect(),
+                    None => out.clone()
+                };
+                if written[successor].as_ref() != Some(&merged) {
+                    written[successor] = Some(merged);
+                    changed = true;
+                }
+            }
+        }
+    }
+    let mut reported = BTreeSet::new();
+    for (block, written) in blocks.iter().zip(written.into_iter()) {
+        let mut written = match written {
+            Some(written) => written,
+            None => continue
+        };
+        for pc in block.start..block.end {
+            for register in reads(&*instructions[pc]) {
+                if !written.contains(&register) && reported.insert((pc, register)) {
+                    warnings.push(Warning::UninitializedRead { pc, register: Value::Register(register).format(registers) });
+                }
+            }
+            written.extend(writes(&*instructions[pc]));
+        }
+    }
+
+    // Find jumps out of the program that are always taken. A conditional jump out is a normal way to halt
+    for block in blocks.iter() {
+        if let Some(Successor::Exit) = block.taken {
+            let pc = block.end - 1;
+            let always = match (instructions[pc].get_type(), instructions[pc].get_operands().first()) {
+                (InstructionType::Jnz, Some(&Value::Literal(condition))) => condition != 0,
+                (InstructionType::Jgz, Some(&Value::Literal(condition))) => condition > 0,
+                _ => false
+            };
+            if let (true, Some(&Value::Literal(offset))) = (always, instructions[pc].get_operands().get(1)) {
+                warnings.push(Warning::LeavesProgram { pc, target: pc as i64 + offset });
+            }
+        }
+    }
+
+    // Find receives that nothing can satisfy
+    if !instructions.iter().any(|instruction| instruction.get_type() == InstructionType::Snd) {
+        warnings.extend(instructions.iter().enumerate()
+            .filter(|&(_, instruction)| instruction.get_type() == InstructionType::Rcv)
+            .map(|(pc, _)| Warning::UnsatisfiableRcv { pc }));
+    }
+
+    warnings.sort_by_key(|warning| warning.pc());
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use processor::instruction;
+    use day18;
+
+    fn lint_program(input: &str, initialized: &[&str]) -> Vec<String> {
+        let mut registers = RegisterTable::new();
+        let instructions = instruction::parse_program(input, &day18::INSTRUCTION_SET, &mut registers).unwrap();
+        lint(&instructions, &registers, initialized).iter().map(|warning| warning.to_string()).collect()
+    }
+
+    #[test]
+    fn test_uninitialized() {
+        // `b` is only written on one path to the `add`, and `c` is a typo for `a`
+        let warnings = lint_program("set a p\njgz a 2\nset b 1\nadd a b\nmul a c", &["p"]);
+        assert_eq!(warnings, vec![
+            "3: register `b` may be read before it is written, it will be 0",
+            "4: register `c` may be read before it is written, it will be 0",
+        ]);
+        // Registers set in a loop are written by the time the loop repeats
+        assert!(lint_program("set a 3\nset b a\nadd a -1\njgz a -2", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let warnings = lint_program("set a 1\njgz 1 3\nset a 2\nset a 3\njgz 1 -5", &[]);
+        assert_eq!(warnings, vec!["2..4: instructions are unreachable", "4: jump to -1 leaves the program"]);
+        // Only jumps that are always taken are reported
+        assert!(lint_program("set a 1\njgz a -2\njgz 0 -3", &[]).is_empty());
+        // A jump through a register might reach anything
+        assert!(lint_program("set a 2\njgz 1 a\nset a 3", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_unsatisfiable_rcv() {
+        assert_eq!(lint_program("set a 1\nrcv a", &[]), vec!["1: rcv can never be satisfied, nothing is ever sent"]);
+        assert!(lint_program("snd 1\nrcv a", &[]).is_empty());
+    }
+}
@@ -1,5 +1,21 @@
 pub mod environment;
 pub mod value;
+pub mod register_table;
+pub mod instruction;
+pub mod debugger;
+pub mod trace;
+pub mod watchdog;
+pub mod profile;
+pub mod optimizer;
+pub mod cfg;
+pub mod decompiler;
+pub mod lint;
+pub mod snapshot;
+pub mod arithmetic;
+pub mod repl;
+pub mod device;
+pub mod cycle;
 
 pub use self::environment::*;
-pub use self::value::*;
\ No newline at end of file
+pub use self::value::*;
+pub use self::register_table::*;
\ No newline at end of file
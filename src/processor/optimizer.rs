@@ -0,0 +1,366 @@
+use super::{Environment, Value};
+use super::instruction::{Instruction, InstructionType};
+use std::collections::{HashMap, HashSet};
+
+/// Loops that count a register down to zero, one at a time
+const COUNTDOWN: [&'static str; 2] = [
+    "sub $counter 1\njnz $counter -1",
+    "sub $counter 1\njgz $counter -1",
+];
+
+/// Loops that multiply by adding `value` to `target` once per count of `counter`
+const REPEATED_ADD: [&'static str; 2] = [
+    "add $target $value\nsub $counter 1\njnz $counter -2",
+    "add $target $value\nsub $counter 1\njgz $counter -2",
+];
+
+/// The same as `REPEATED_ADD`, but subtracting `value` from `target`
+const REPEATED_SUB: [&'static str; 2] = [
+    "sub $target $value\nsub $counter 1\njnz $counter -2",
+    "sub $target $value\nsub $counter 1\njgz $counter -2",
+];
+
+/// A loop over every `factor` up to `number`, clearing `flag` if `divisor * factor == number`
+const DIVISOR_TEST: &'static str = "\
+set $scratch $divisor
+mul $scratch $factor
+sub $scratch $number
+jnz $scratch 2
+set $flag 0
+sub $factor -1
+set $scratch $factor
+sub $scratch $number
+jnz $scratch -8";
+
+/// A loop over every `divisor` up to `number`, running a `DIVISOR_TEST` with `factor` starting from `start`
+const NESTED_DIVISOR_TEST: &'static str = "\
+set $factor $start
+set $scratch $divisor
+mul $scratch $factor
+sub $scratch $number
+jnz $scratch 2
+set $flag 0
+sub $factor -1
+set $scratch $factor
+sub $scratch $number
+jnz $scratch -8
+sub $divisor -1
+set $scratch $divisor
+sub $scratch $number
+jnz $scratch -13";
+
+/// A loop that can be executed in one go
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Idiom {
+    /// `counter` is set to 0
+    Countdown { counter: usize },
+    /// `value * counter` is added to `target` (or subtracted if `sign` is -1), then `counter` is set to 0
+    RepeatedAdd { target: usize, value: Value, counter: usize, sign: i64 },
+    /// `flag` is cleared if `number` is `divisor` times some `factor` below `number`
+    DivisorTest { flag: usize, divisor: Value, factor: usize, number: Value, scratch: usize },
+    /// `flag` is cleared if `number` is the product of a `divisor` and a factor from `start`, both below `number`
+    NestedDivisorTest { flag: usize, divisor: usize, factor: usize, start: Value, number: Value, scratch: usize },
+}
+
+impl Idiom {
+    /// The number of instructions the idiom replaces
+    fn len(&self) -> i64 {
+        match *self {
+            Idiom::Countdown { .. } => 2,
+            Idiom::RepeatedAdd { .. } => 3,
+            Idiom::DivisorTest { .. } => 9,
+            Idiom::NestedDivisorTest { .. } => 14,
+        }
+    }
+
+    /// Executes the whole loop and moves the PC past it.
+    /// Returns false without touching the environment if the loop would not run as expected,
+    ///  e.g. a countdown from a negative number that relies on wrapping around
+    fn apply(&self, environment: &mut Environment) -> bool {
+        // Registers beyond an i64 are left to the instructions, as are loops that would overflow,
+        //  so the arithmetic mode decides what happens
+        if environment.has_wide_registers() {
+            return false;
+        }
+        match *self {
+            Idiom::Countdown { counter } => {
+                if environment.get_register(counter) <= 0 {
+                    return false;
+                }
+                environment.set_register(counter, 0);
+            },
+            Idiom::RepeatedAdd { target, value, counter, sign } => {
+                let count = environment.get_register(counter);
+                if count <= 0 {
+                    return false;
+                }
+                // Every addition has the same sign, so if the total fits so does every step towards it
+                let result = match environment.get_value(&value).checked_mul(sign * count).and_then(|total| total.checked_add(environment.get_register(target))) {
+                    Some(result) => result,
+                    None => return false
+                };
+                environment.set_register(target, result);
+                environment.set_register(counter, 0);
+            },
+            Idiom::DivisorTest { flag, divisor, factor, number, scratch } => {
+                let (divisor, first, number) = (environment.get_value(&divisor), environment.get_register(factor), environment.get_value(&number));
+                if divisor < 1 || first < 1 || first >= number || divisor.checked_mul(number).is_none() {
+                    return false;
+                }
+                let quotient = number / divisor;
+                if number % divisor == 0 && quotient >= first && quotient < number {
+                    environment.set_register(flag, 0);
+                }
+                environment.set_register(factor, number);
+                environment.set_register(scratch, 0);
+            },
+            Idiom::NestedDivisorTest { flag, divisor, factor, start, number, scratch } => {
+                let (first_divisor, first_factor, number) = (environment.get_register(divisor), environment.get_value(&start), environment.get_value(&number));
+                if first_divisor < 1 || first_factor < 1 || first_divisor >= number || first_factor >= number || number.checked_mul(number).is_none() {
+                    return false;
+                }
+                if has_factors(number, first_divisor, first_factor) {
+                    environment.set_register(flag, 0);
+                }
+                environment.set_register(divisor, number);
+                environment.set_register(factor, number);
+                environment.set_register(scratch, 0);
+            },
+        }
+        environment.jump_pc(self.len());
+        true
+    }
+}
+
+/// Checks whether `number` is `a * b` for some `a` in [`min_a`, `number`) and `b` in [`min_b`, `number`)
+fn has_factors(number: i64, min_a: i64, min_b: i64) -> bool {
+    let fits = |a: i64, b: i64| a >= min_a && a < number && b >= min_b && b < number;
+    let mut i = 1;
+    while i * i <= number {
+        if number % i == 0 && (fits(i, number / i) || fits(number / i, i)) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// The first instruction of a recognised loop.
+/// Runs the whole loop when it can, otherwise behaves exactly like the instruction it replaced
+#[derive(Debug)]
+struct Fused {
+    original: Box<Instruction>,
+    idiom: Idiom,
+}
+
+impl Instruction for Fused {
+    fn get_type(&self) -> InstructionType {
+        self.original.get_type()
+    }
+
+    fn get_operands(&self) -> Vec<Value> {
+        self.original.get_operands()
+    }
+
+    fn execute(&self, environment: &mut Environment) -> Option<i64> {
+        if self.idiom.apply(environment) {
+            return None;
+        }
+        self.original.execute(environment)
+    }
+}
+
+/// Gets the type and operands of an instruction, writing `add <register> <literal>` as the equivalent `sub`.
+/// Adding the smallest i64 has no equivalent `sub`, so it is left as an `add`
+fn normalize(instruction: &Instruction) -> (InstructionType, Vec<Value>) {
+    let operands = instruction.get_operands();
+    match (instruction.get_type(), operands.as_slice()) {
+        (InstructionType::Add, &[register, Value::Literal(value)]) => match value.checked_neg() {
+            Some(negated) => (InstructionType::Sub, vec![register, Value::Literal(negated)]),
+            None => (InstructionType::Add, operands.clone())
+        },
+        (instruction_type, _) => (instruction_type, operands.clone())
+    }
+}
+
+/// Matches the instructions starting at `pc` against `template`, an assembly listing where operands
+///  starting with `$` stand for any register or literal. Every use of a name must stand for the same
+///  operand, and different names must stand for different operands.
+/// Returns the operand each name stands for
+fn bind<'a>(template: &'a str, instructions: &[Box<Instruction>], pc: usize) -> Option<HashMap<&'a str, Value>> {
+    let mut bindings = HashMap::new();
+    for (offset, line) in template.split("\n").enumerate() {
+        let (instruction_type, operands) = normalize(&**instructions.get(pc + offset)?);
+        let parts = line.split_whitespace().collect::<Vec<&str>>();
+        if parts[0] != instruction_type.get_name() || parts.len() != operands.len() + 1 {
+            return None;
+        }
+        for (&part, &operand) in parts[1..].iter().zip(operands.iter()) {
+            if part.starts_with("$") {
+                if *bindings.entry(&part[1..]).or_insert(operand) != operand {
+                    return None;
+                }
+            } else if part.parse::<i64>().ok().map(Value::Literal) != Some(operand) {
+                return None;
+            }
+        }
+    }
+    let distinct = bindings.values().collect::<HashSet<&Value>>();
+    if distinct.len() != bindings.len() {
+        return None;
+    }
+    Some(bindings)
+}
+
+/// Gets the slot of a register bound by a template
+fn register(bindings: &HashMap<&str, Value>, name: &str) -> Option<usize> {
+    match bindings.get(name) {
+        Some(&Value::Register(slot)) => Some(slot),
+        _ => None
+    }
+}
+
+/// Finds the idiom, if any, of the loop starting at `pc`
+fn recognise(instructions: &[Box<Instruction>], pc: usize) -> Option<Idiom> {
+    if let Some(found) = bind(NESTED_DIVISOR_TEST, instructions, pc) {
+        return Some(Idiom::NestedDivisorTest {
+            flag: register(&found, "flag")?,
+            divisor: register(&found, "divisor")?,
+            factor: register(&found, "factor")?,
+            start: found["start"],
+            number: found["number"],
+            scratch: register(&found, "scratch")?,
+        });
+    }
+    if let Some(found) = bind(DIVISOR_TEST, instructions, pc) {
+        return Some(Idiom::DivisorTest {
+            flag: register(&found, "flag")?,
+            divisor: found["divisor"],
+            factor: register(&found, "factor")?,
+            number: found["number"],
+            scratch: register(&found, "scratch")?,
+        });
+    }
+    for (templates, sign) in [(&REPEATED_ADD, 1), (&REPEATED_SUB, -1)].iter() {
+        if let Some(found) = templates.iter().filter_map(|template| bind(template, instructions, pc)).next() {
+            return Some(Idiom::RepeatedAdd { target: register(&found, "target")?, value: found["value"], counter: register(&found, "counter")?, sign: *sign });
+        }
+    }
+    if let Some(found) = COUNTDOWN.iter().filter_map(|template| bind(template, instructions, pc)).next() {
+        return Some(Idiom::Countdown { counter: register(&found, "counter")? });
+    }
+    None
+}
+
+/// Replaces the first instruction of every recognised loop with one that runs the whole loop at once.
+///
+/// The optimised program has the same number of instructions at the same offsets, so jumps and
+///  disassembly are unaffected, and computes the same register values. Only the number of
+///  instructions executed changes, since a fused loop counts as a single instruction
+pub fn optimize(instructions: Vec<Box<Instruction>>) -> Vec<Box<Instruction>> {
+    let idioms = (0..instructions.len()).map(|pc| recognise(&instructions, pc)).collect::<Vec<Option<Idiom>>>();
+    instructions.into_iter().zip(idioms.into_iter()).map(|(original, idiom)| match idiom {
+        Some(idiom) => Box::new(Fused { original, idiom }) as Box<Instruction>,
+        None => original
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use processor::RegisterTable;
+    use processor::instruction;
+    use day23::Coprocessor;
+
+    const INSTRUCTION_SET: [InstructionType; 7] = [
+        InstructionType::Set,
+        InstructionType::Add,
+        InstructionType::Sub,
+        InstructionType::Mul,
+        InstructionType::Mod,
+        InstructionType::Jgz,
+        InstructionType::Jnz
+    ];
+
+    /// The day 23 program, counting the composite numbers between `b` and `c`
+    const COMPOSITES: &'static str = "set b 57\nset c b\njnz a 2\njnz 1 5\nmul b 100\nsub b -100000\nset c b\nsub c -17000\n\
+        set f 1\nset d 2\nset e 2\nset g d\nmul g e\nsub g b\njnz g 2\nset f 0\nsub e -1\nset g e\nsub g b\njnz g -8\n\
+        sub d -1\nset g d\nsub g b\njnz g -13\njnz f 2\nsub h -1\nset g b\nsub g c\njnz g 2\njnz 1 3\nsub b -17\njnz 1 -23";
+
+    fn idioms(input: &str) -> Vec<Option<Idiom>> {
+        let instructions = instruction::parse_program(input, &INSTRUCTION_SET, &mut RegisterTable::new()).unwrap();
+        (0..instructions.len()).map(|pc| recognise(&instructions, pc)).collect()
+    }
+
+    #[test]
+    fn test_recognise() {
+        assert_eq!(idioms("sub a 1\njnz a -1\nadd b -1\njgz b -1"), vec![
+            Some(Idiom::Countdown { counter: 0 }), None, Some(Idiom::Countdown { counter: 1 }), None
+        ]);
+        assert_eq!(idioms("add a b\nadd c -1\njnz c -2")[0], Some(Idiom::RepeatedAdd { target: 0, value: Value::Register(1), counter: 2, sign: 1 }));
+        assert_eq!(idioms("sub a 3\nsub c 1\njgz c -2")[0], Some(Idiom::RepeatedAdd { target: 0, value: Value::Literal(3), counter: 1, sign: -1 }));
+        // The counter can't also be the value being added
+        assert_eq!(idioms("add a c\nsub c 1\njnz c -2")[0], None);
+        // Adding the smallest i64 can't be negated into a `sub`
+        assert_eq!(idioms("add a -9223372036854775808\njnz a -1"), vec![None, None]);
+
+        let found = idioms(COMPOSITES);
+        assert_eq!(found.iter().filter(|idiom| idiom.is_some()).count(), 2);
+        assert!(match found[10] { Some(Idiom::NestedDivisorTest { .. }) => true, _ => false });
+        assert!(match found[11] { Some(Idiom::DivisorTest { .. }) => true, _ => false });
+    }
+
+    #[test]
+    fn test_optimize() {
+        let input = "set a 2\nset b 3\nset c 4\nadd a b\nadd c -1\njnz c -2\nset c 5\nsub a 1\nsub c 1\njnz c -2\nset d 3\nsub d 1\njnz d -1";
+        let run = |optimized: bool| {
+            let mut registers = RegisterTable::new();
+            let mut instructions = instruction::parse_program(input, &INSTRUCTION_SET, &mut registers).unwrap();
+            if optimized {
+                instructions = optimize(instructions);
+            }
+            let mut environment = Environment::with_registers(registers);
+            let mut steps = 0;
+            while environment.get_pc() >= 0 && environment.get_pc() < instructions.len() as i64 {
+                let pc = environment.get_pc() as usize;
+                environment.step_pc();
+                instructions[pc].execute(&mut environment);
+                steps += 1;
+            }
+            (environment.registers(), steps)
+        };
+        let (expected, _) = run(false);
+        assert_eq!(run(true), (expected, 8));
+        assert_eq!(run(true).0[0], (String::from("a"), 9));
+    }
+
+    #[test]
+    fn test_fallback() {
+        // Counting down from 0 relies on wrapping around, so the loop runs one instruction at a time
+        let mut registers = RegisterTable::new();
+        let instructions = optimize(instruction::parse_program("sub a 1\njnz a -1", &INSTRUCTION_SET, &mut registers).unwrap());
+        let mut environment = Environment::with_registers(registers);
+        environment.step_pc();
+        instructions[0].execute(&mut environment);
+        assert_eq!(environment.get(&"a"), -1);
+        assert_eq!(environment.get_pc(), 1);
+    }
+
+    #[test]
+    fn test_composites() {
+        // A single number is cheap enough to check against the unoptimised program
+        let mut plain = Coprocessor::new(COMPOSITES).unwrap();
+        plain.execute().unwrap();
+        let mut optimized = Coprocessor::new(COMPOSITES).unwrap();
+        optimized.optimize();
+        optimized.execute().unwrap();
+        assert_eq!(optimized.get_state().registers, plain.get_state().registers);
+
+        let mut optimized = Coprocessor::new(COMPOSITES).unwrap();
+        optimized.optimize();
+        optimized.set_register("a", 1);
+        optimized.execute().unwrap();
+        // The number of composites among 105700, 105717, ..., 122700
+        assert_eq!(optimized.get_register("h"), 915);
+    }
+}
@@ -0,0 +1,153 @@
+use super::instruction::InstructionType;
+use super::debugger::Debuggable;
+use std::collections::{BTreeMap, HashMap};
+
+/// The number of most executed lines marked in an annotated listing
+const HOT_LINES: usize = 3;
+
+/// Counts how often each line and each kind of instruction of a program is executed.
+///
+/// A loop is identified by a taken jump to the same or an earlier PC, and spans from the
+///  target of the jump to the jump itself. The cycles spent in a loop are the steps executed
+///  from the target up to each jump back, including those of any nested loops
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Profile {
+    lines: Vec<usize>,
+    instructions: HashMap<InstructionType, usize>,
+    /// The iterations and cycles of each loop
+    loops: BTreeMap<(i64, i64), (usize, usize)>,
+    /// The step at which each line last executed, 0 if it never has
+    last_steps: Vec<usize>,
+    steps: usize,
+}
+
+/// A loop found while profiling
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Loop {
+    /// The first instruction of the loop
+    pub start: i64,
+    /// The jump back to `start`
+    pub end: i64,
+    /// The number of times the jump back was taken
+    pub iterations: usize,
+    pub cycles: usize,
+}
+
+impl Profile {
+    /// Creates an empty profile for a program of `length` instructions
+    pub fn new(length: usize) -> Profile {
+        Profile { lines: vec![0; length], instructions: HashMap::new(), loops: BTreeMap::new(), last_steps: vec![0; length], steps: 0 }
+    }
+
+    /// Records the execution of the instruction at `pc`, which moved the PC to `next_pc`.
+    /// A `rcv` without a result was blocked and didn't execute, so it is ignored
+    pub fn record(&mut self, pc: i64, instruction_type: InstructionType, result: Option<i64>, next_pc: i64) {
+        if instruction_type == InstructionType::Rcv && result == None {
+            return;
+        }
+        self.steps += 1;
+        if let Some(count) = self.lines.get_mut(pc as usize) {
+            *count += 1;
+        }
+        if let Some(step) = self.last_steps.get_mut(pc as usize) {
+            *step = self.steps;
+        }
+        *self.instructions.entry(instruction_type).or_insert(0) += 1;
+        // A jump to before the start of the program halts rather than looping
+        if 0 <= next_pc && next_pc <= pc {
+            // The iteration started when the target last executed. A loop entered part way started with
+            //  the first of its lines to execute
+            let started = match self.last_steps.get(next_pc as usize).cloned().unwrap_or(0) {
+                0 => self.last_steps.get(next_pc as usize..pc as usize + 1).unwrap_or(&[]).iter().cloned().filter(|&step| step > 0).min().unwrap_or(self.steps),
+                step => step
+            };
+            let found = self.loops.entry((next_pc, pc)).or_insert((0, 0));
+            found.0 += 1;
+            found.1 += self.steps - started + 1;
+        }
+    }
+
+    /// Gets the number of times the instruction at `pc` was executed
+    pub fn line(&self, pc: i64) -> usize {
+        self.lines.get(pc as usize).cloned().unwrap_or(0)
+    }
+
+    /// Gets the number of times instructions of `instruction_type` were executed
+    pub fn count(&self, instruction_type: InstructionType) -> usize {
+        self.instructions.get(&instruction_type).cloned().unwrap_or(0)
+    }
+
+    /// Gets the total number of instructions executed
+    pub fn total(&self) -> usize {
+        self.lines.iter().sum()
+    }
+
+    /// Gets every loop that was entered, ordered by its first instruction
+    pub fn loops(&self) -> Vec<Loop> {
+        self.loops.iter().map(|(&(start, end), &(iterations, cycles))| Loop { start, end, iterations, cycles }).collect()
+    }
+
+    /// Formats the program run by `machine` with the execution count of every line,
+    ///  marking the hottest lines with `*`, followed by the loops and instruction counts
+    pub fn annotate(&self, machine: &Debuggable) -> String {
+        let mut hottest = self.lines.iter().cloned().filter(|&count| count > 0).collect::<Vec<usize>>();
+        hottest.sort_by(|a, b| b.cmp(a));
+        let threshold = hottest.get(HOT_LINES.min(hottest.len()).saturating_sub(1)).cloned().unwrap_or(1);
+
+        let mut output = vec![String::from("   count   pc  instruction")];
+        for (pc, &count) in self.lines.iter().enumerate() {
+            let marker = if count >= threshold && count > 0 { "*" } else { " " };
+            let instruction = machine.disassemble(pc as i64).unwrap_or_default();
+            output.push(format!("{} {:>7} {:>4}  {}", marker, count, pc, instruction));
+        }
+
+        output.push(String::from("loops:"));
+        for found in self.loops() {
+            output.push(format!("  {}..{}: {} iterations, {} cycles", found.start, found.end, found.iterations, found.cycles));
+        }
+
+        output.push(String::from("instructions:"));
+        let mut instructions = self.instructions.iter().map(|(&instruction_type, &count)| (instruction_type, count)).collect::<Vec<(InstructionType, usize)>>();
+        instructions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.get_name().cmp(b.0.get_name())));
+        for (instruction_type, count) in instructions {
+            output.push(format!("  {} {}", instruction_type.get_name(), count));
+        }
+        output.push(format!("  total {}", self.total()));
+        output.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use day23::Coprocessor;
+
+    #[test]
+    fn test_profile() {
+        // An outer loop running 2 times around an inner loop counting down from 3
+        let input = "set b 2\nset a 3\nsub a 1\njnz a -1\nsub b 1\njnz b -4";
+        let mut coprocessor = Coprocessor::new(input).unwrap();
+        coprocessor.execute().unwrap();
+        let profile = coprocessor.profile();
+        assert_eq!((0..6).map(|pc| profile.line(pc)).collect::<Vec<usize>>(), vec![1, 2, 6, 6, 2, 2]);
+        assert_eq!(profile.count(InstructionType::Sub), 8);
+        assert_eq!(profile.total(), 19);
+        assert_eq!(profile.loops(), vec![
+            Loop { start: 1, end: 5, iterations: 1, cycles: 9 },
+            Loop { start: 2, end: 3, iterations: 4, cycles: 8 },
+        ]);
+
+        let listing = profile.annotate(&coprocessor);
+        assert!(listing.contains("*       6    2  sub a 1\n"));
+        assert!(listing.contains("        1    0  set b 2\n"));
+        assert!(listing.contains("loops:\n  1..5: 1 iterations, 9 cycles\n  2..3: 4 iterations, 8 cycles\n"));
+        assert!(listing.contains("instructions:\n  jnz 8\n  sub 8\n  set 3\n  total 19"));
+
+        // Lines in the range of a loop only count towards it when they run between its target and its jump back,
+        //  and a jump to before the program halts
+        let input = "set a 2\njnz 1 2\nsub b -1\nsub a 1\njnz a -2\njnz 1 -6";
+        let mut coprocessor = Coprocessor::new(input).unwrap();
+        coprocessor.execute().unwrap();
+        assert_eq!(coprocessor.profile().loops(), vec![Loop { start: 2, end: 4, iterations: 1, cycles: 2 }]);
+    }
+}
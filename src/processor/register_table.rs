@@ -0,0 +1,55 @@
+/// Maps register names onto slots in an `Environment`'s register array.
+/// Programs are lowered against a table when they are parsed so that executing
+///  an instruction never has to look up a register by name
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegisterTable {
+    names: Vec<String>,
+}
+
+impl RegisterTable {
+    pub fn new() -> RegisterTable {
+        RegisterTable { names: Vec::new() }
+    }
+
+    /// Gets the slot of a register, allocating a new slot if the register hasn't been seen before
+    pub fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.find(name) {
+            return slot;
+        }
+        self.names.push(name.into());
+        self.names.len() - 1
+    }
+
+    /// Gets the slot of a register if it has been allocated
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Gets the name of the register stored in `slot`
+    pub fn name(&self, slot: usize) -> Option<&str> {
+        self.names.get(slot).map(|name| name.as_str())
+    }
+
+    /// The number of allocated slots
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slot() {
+        let mut table = RegisterTable::new();
+        assert_eq!(table.slot("a"), 0);
+        assert_eq!(table.slot("b"), 1);
+        assert_eq!(table.slot("a"), 0);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.find("b"), Some(1));
+        assert_eq!(table.find("c"), None);
+        assert_eq!(table.name(1), Some("b"));
+        assert_eq!(table.name(2), None);
+    }
+}
\ No newline at end of file
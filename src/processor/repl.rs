@@ -0,0 +1,246 @@
+use super::environment::Environment;
+use super::instruction::{self, Instruction, InstructionType};
+use super::watchdog::{Limits, Watchdog};
+use super::arithmetic::{Arithmetic, BigInt};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+const HELP: &'static str = "\
+type an instruction to execute it, or a command:
+  :load <file>        load a program to run from pc 0
+  :run                run the loaded program from the current pc until it halts or blocks
+  :regs               print the pc, queue and registers
+  :reset              set the pc and every register back to 0 and empty the queue
+  :quit";
+
+/// An environment that instructions can be typed into one at a time.
+/// Values sent with `snd` are queued to be received by the same environment, so that
+///  `snd` and `rcv` can be tried out without a second program
+pub struct Repl {
+    instruction_set: &'static [InstructionType],
+    environment: Environment,
+    program: Vec<Box<Instruction>>,
+    limits: Limits,
+}
+
+impl Repl {
+    pub fn new(instruction_set: &'static [InstructionType]) -> Repl {
+        Repl { instruction_set, environment: Environment::new(), program: Vec::new(), limits: Limits::none() }
+    }
+
+    /// Stops `:run` once it exceeds `limits`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets what happens when arithmetic overflows
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.environment.set_arithmetic(arithmetic);
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Replaces the loaded program with `source` and moves the PC to its start.
+    /// Returns the number of instructions loaded
+    pub fn load(&mut self, source: &str) -> Result<usize, String> {
+        let program = instruction::parse_program(source, self.instruction_set, self.environment.get_register_table_mut())
+            .map_err(|error| error.to_string())?;
+        self.program = program;
+        self.environment.set_pc(0);
+        Ok(self.program.len())
+    }
+
+    /// Sets the PC and every register back to 0 and empties the queue.
+    /// The loaded program and arithmetic mode are kept
+    pub fn reset(&mut self) {
+        let table = self.environment.get_register_table().clone();
+        let arithmetic = self.environment.get_arithmetic();
+        self.environment = Environment::with_registers(table);
+        self.environment.set_arithmetic(arithmetic);
+    }
+
+    /// Runs a session reading instructions and commands from `input` and writing results to `output`
+    pub fn run_session<R, W>(&mut self, input: R, output: &mut W) -> io::Result<()>
+        where R: BufRead, W: Write {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let parts = line.split_whitespace().collect::<Vec<&str>>();
+            match parts.as_slice() {
+                [] => {},
+                [":load", path] => match read_file(path) {
+                    Ok(source) => match self.load(&source) {
+                        Ok(count) => writeln!(output, "loaded {} instructions", count)?,
+                        Err(error) => writeln!(output, "{}", error)?
+                    },
+                    Err(error) => writeln!(output, "Could not read program: {}", error)?
+                },
+                [":run"] => self.run(output)?,
+                [":regs"] => self.print_registers(output)?,
+                [":reset"] => {
+                    self.reset();
+                    writeln!(output, "reset")?;
+                },
+                [":quit"] => return Ok(()),
+                _ if line.trim().starts_with(":") => writeln!(output, "{}", HELP)?,
+                _ => self.execute(&line, output)?
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Executes a typed instruction at the current PC, printing what it changed.
+    /// The PC only moves if the instruction jumps, even if the jump is to the next instruction
+    fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<()> {
+        let instruction = match instruction::parse(line, self.instruction_set, self.environment.get_register_table_mut()) {
+            Ok(instruction) => instruction,
+            Err(reason) => return writeln!(output, "{}", reason)
+        };
+        let before = self.environment.exact_registers();
+        let pc = self.environment.get_pc();
+        self.environment.step_pc();
+        let result = instruction.execute(&mut self.environment);
+        let jumped = match (instruction.get_type(), result) {
+            (InstructionType::Jgz, Some(_)) | (InstructionType::Jnz, Some(_)) => true,
+            _ => false
+        };
+        if !jumped {
+            self.environment.set_pc(pc);
+        }
+        match (instruction.get_type(), result) {
+            (InstructionType::Snd, Some(value)) => {
+                self.environment.enqueue(value);
+                writeln!(output, "sent {}", value)?;
+            },
+            (InstructionType::Rcv, None) => writeln!(output, "nothing to receive")?,
+            (InstructionType::Recover, Some(frequency)) => writeln!(output, "recovered {}", frequency)?,
+            _ => {}
+        }
+        if let Some(fault) = self.environment.take_fault(pc) {
+            writeln!(output, "{}", fault)?;
+        }
+        self.print_changes(&before, output)?;
+        if self.environment.get_pc() != pc {
+            writeln!(output, "pc = {}", self.environment.get_pc())?;
+        }
+        Ok(())
+    }
+
+    /// Runs the loaded program until it halts, blocks on a `rcv`, recovers a sound, fails or
+    ///  exceeds its limits, printing why it stopped and what it changed
+    fn run<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        let before = self.environment.exact_registers();
+        let mut watchdog = Watchdog::new(self.limits);
+        let mut steps = 0;
+        let stop = loop {
+            let pc = self.environment.get_pc();
+            if pc < 0 || pc >= self.program.len() as i64 {
+                break format!("halted at pc {} after {} steps", pc, steps);
+            }
+            if let Err(fault) = watchdog.tick() {
+                break format!("interrupted: {}", fault);
+            }
+            let instruction = &self.program[pc as usize];
+            self.environment.step_pc();
+            let result = instruction.execute(&mut self.environment);
+            if let Some(fault) = self.environment.take_fault(pc) {
+                self.environment.set_pc(pc);
+                break format!("interrupted: {}", fault);
+            }
+            match (instruction.get_type(), result) {
+                (InstructionType::Snd, Some(value)) => self.environment.enqueue(value),
+                (InstructionType::Rcv, None) => break format!("blocked on rcv at pc {} after {} steps", pc, steps),
+                (InstructionType::Recover, Some(frequency)) => break format!("recovered {} at pc {}", frequency, pc),
+                _ => {}
+            }
+            steps += 1;
+        };
+        writeln!(output, "{}", stop)?;
+        self.print_changes(&before, output)
+    }
+
+    /// Prints the registers whose values differ from `before`
+    fn print_changes<W: Write>(&self, before: &[(String, BigInt)], output: &mut W) -> io::Result<()> {
+        for (slot, &(ref name, ref value)) in self.environment.exact_registers().iter().enumerate() {
+            // Registers first used by the instruction start at 0
+            let previous = before.get(slot).map_or(BigInt::from(0), |&(_, ref value)| value.clone());
+            if previous != *value {
+                writeln!(output, "{} = {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_registers<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "pc = {}", self.environment.get_pc())?;
+        let queue = self.environment.queue();
+        if !queue.is_empty() {
+            writeln!(output, "queue = {:?}", queue)?;
+        }
+        for (name, value) in self.environment.exact_registers() {
+            writeln!(output, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_file(path: &str) -> io::Result<String> {
+    let mut input = String::new();
+    File::open(path)?.read_to_string(&mut input)?;
+    Ok(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use day18;
+    use day23;
+
+    fn session(repl: &mut Repl, input: &str) -> String {
+        let mut output = Vec::new();
+        repl.run_session(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_instructions() {
+        let mut repl = Repl::new(&day18::INSTRUCTION_SET);
+        let output = session(&mut repl, "set a 3\nmul a a\nsnd a\nrcv b\nrcv b\njgz a 4\nfoo\n:regs\n:reset\n:regs\n");
+        assert_eq!(output, "> a = 3\n\
+            > a = 9\n\
+            > sent 9\n\
+            > b = 9\n\
+            > nothing to receive\n\
+            > pc = 4\n\
+            > unknown opcode `foo`\n\
+            > pc = 4\na = 9\nb = 9\n\
+            > reset\n\
+            > pc = 0\na = 0\nb = 0\n\
+            > ");
+
+        // A jump to the next instruction still moves the PC
+        let mut repl = Repl::new(&day23::INSTRUCTION_SET);
+        assert_eq!(session(&mut repl, "jnz 1 1\njnz 0 5\n"), "> pc = 1\n> > ");
+    }
+
+    #[test]
+    fn test_run() {
+        let mut repl = Repl::new(&day23::INSTRUCTION_SET);
+        assert_eq!(repl.load("set b 5\nsub b 1\njnz b -1"), Ok(3));
+        let output = session(&mut repl, "set a 2\n:run\n:run\n:load /nonexistent/program\n:help");
+        assert!(output.starts_with("> a = 2\n> halted at pc 3 after 11 steps\n> halted at pc 3 after 0 steps\n> Could not read program: "));
+        assert!(output.ends_with(&format!("{}\n> ", HELP)));
+        assert_eq!(repl.environment().get_register_table().find("b"), Some(0));
+
+        // Runs are held to the limits
+        repl.reset();
+        repl.set_limits(Limits::steps(4));
+        let output = session(&mut repl, ":run\n");
+        assert_eq!(output, "> interrupted: step limit of 4 reached\nb = 3\n> ");
+    }
+}
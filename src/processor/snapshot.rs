@@ -0,0 +1,202 @@
+use super::environment::Environment;
+use super::register_table::RegisterTable;
+use super::arithmetic::BigInt;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::convert::TryFrom;
+
+/// The saved state of one environment.
+/// `links` are indices into the environments of the enclosing `Snapshot`.
+/// Registers are saved exactly, even if they have outgrown an i64
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EnvironmentSnapshot {
+    pub pc: i64,
+    pub registers: Vec<(String, BigInt)>,
+    pub queue: Vec<i64>,
+    pub links: Vec<usize>,
+}
+
+impl EnvironmentSnapshot {
+    /// Recreates the environment, without its links, with registers allocated from `table`.
+    /// Registers that aren't in `table` are added to it
+    pub fn restore(&self, table: RegisterTable) -> Environment {
+        let mut environment = Environment::with_registers(table);
+        environment.set_pc(self.pc);
+        for &(ref name, ref value) in self.registers.iter() {
+            environment.set_exact(name, value.clone());
+        }
+        for &value in self.queue.iter() {
+            environment.enqueue(value);
+        }
+        environment
+    }
+}
+
+impl Display for EnvironmentSnapshot {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let registers = self.registers.iter().map(|&(ref name, ref value)| format!("{}:{}", name, value)).collect::<Vec<String>>();
+        let queue = self.queue.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+        let links = self.links.iter().map(|link| link.to_string()).collect::<Vec<String>>();
+        write!(f, "environment pc={} registers={} queue={} links={}", self.pc, registers.join(","), queue.join(","), links.join(","))
+    }
+}
+
+/// The saved state of a group of linked environments, e.g. the programs of a duet.
+///
+/// Snapshots are written one environment per line as
+///  `environment pc=<pc> registers=<name>:<value>,... queue=<value>,... links=<index>,...`
+///  where a link is the index of the line of the environment being sent to
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Snapshot {
+    pub environments: Vec<EnvironmentSnapshot>,
+}
+
+impl Snapshot {
+    /// Saves the state of `environments`.
+    /// Links to environments outside of `environments` can't be restored, so they are left out
+    pub fn capture(environments: &[&Environment]) -> Snapshot {
+        let environments = environments.iter().map(|environment| {
+            let links = environment.links().iter()
+                .filter_map(|link| environments.iter().position(|&other| other as *const Environment == link.as_ptr() as *const Environment))
+                .collect();
+            EnvironmentSnapshot { pc: environment.get_pc(), registers: environment.exact_registers(), queue: environment.queue(), links }
+        }).collect();
+        Snapshot { environments }
+    }
+
+    /// Recreates the environments and links between them, with registers allocated from `table`
+    pub fn restore(&self, table: &RegisterTable) -> Vec<Rc<RefCell<Environment>>> {
+        let environments = self.environments.iter()
+            .map(|environment| Rc::new(RefCell::new(environment.restore(table.clone()))))
+            .collect::<Vec<Rc<RefCell<Environment>>>>();
+        for (environment, snapshot) in environments.iter().zip(self.environments.iter()) {
+            for &link in snapshot.links.iter() {
+                environment.borrow_mut().link(environments[link].clone());
+            }
+        }
+        environments
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let lines = self.environments.iter().map(|environment| environment.to_string()).collect::<Vec<String>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Splits a comma separated list, where an empty string is an empty list
+fn split_list(input: &str) -> Vec<&str> {
+    input.split(",").filter(|item| !item.is_empty()).collect()
+}
+
+impl <'a> TryFrom<&'a str> for EnvironmentSnapshot {
+    type Error = ParseError<'a>;
+    fn try_from(input: &'a str) -> Result<EnvironmentSnapshot, Self::Error> {
+        let parts = input.split_whitespace().collect::<Vec<&str>>();
+        let field = |index: usize, name: &str| -> Result<&'a str, ParseError<'a>> {
+            let part = parts.get(index).ok_or(ParseError::InvalidFormat(input))?;
+            if !part.starts_with(name) || !part[name.len()..].starts_with("=") {
+                return Err(ParseError::InvalidFormat(input));
+            }
+            Ok(&part[name.len() + 1..])
+        };
+        if parts.len() != 5 || parts[0] != "environment" {
+            return Err(ParseError::InvalidFormat(input));
+        }
+        let parse_value = |value: &'a str| value.parse::<i64>().map_err(|_| ParseError::NotANumber(input, value));
+        let pc = parse_value(field(1, "pc")?)?;
+        let registers = split_list(field(2, "registers")?).into_iter().map(|register| {
+            let i = register.find(":").ok_or(ParseError::InvalidFormat(input))?;
+            let value = &register[i + 1..];
+            Ok((String::from(&register[..i]), value.parse::<BigInt>().map_err(|_| ParseError::NotANumber(input, value))?))
+        }).collect::<Result<Vec<(String, BigInt)>, ParseError>>()?;
+        let queue = split_list(field(3, "queue")?).into_iter().map(parse_value).collect::<Result<Vec<i64>, ParseError>>()?;
+        let links = split_list(field(4, "links")?).into_iter()
+            .map(|link| link.parse::<usize>().map_err(|_| ParseError::NotANumber(input, link)))
+            .collect::<Result<Vec<usize>, ParseError>>()?;
+        Ok(EnvironmentSnapshot { pc, registers, queue, links })
+    }
+}
+
+impl <'a> TryFrom<&'a str> for Snapshot {
+    type Error = ParseError<'a>;
+    /// Parses newline separated environments, ignoring blank lines
+    fn try_from(input: &'a str) -> Result<Snapshot, Self::Error> {
+        let environments = input.split("\n")
+            .filter(|line| !line.trim().is_empty())
+            .map(EnvironmentSnapshot::try_from)
+            .collect::<Result<Vec<EnvironmentSnapshot>, ParseError>>()?;
+        if let Some(link) = environments.iter().flat_map(|environment| environment.links.iter()).find(|&&link| link >= environments.len()) {
+            return Err(ParseError::InvalidLink(*link));
+        }
+        Ok(Snapshot { environments })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    InvalidFormat(&'a str),
+    NotANumber(&'a str, &'a str),
+    InvalidLink(usize),
+}
+
+impl <'a> Display for ParseError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidFormat(input) => write!(f, "Expected `environment pc=.. registers=.. queue=.. links=..`: {}", input),
+            ParseError::NotANumber(input, nan) => write!(f, "Could not parse `{}` into a number in {}", nan, input),
+            ParseError::InvalidLink(link) => write!(f, "Link to environment {} which isn't in the snapshot", link),
+        }
+    }
+}
+
+impl <'a> Error for ParseError<'a> {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::InvalidFormat(_) => "Invalid snapshot",
+            ParseError::NotANumber(_, _) => "Not a number",
+            ParseError::InvalidLink(_) => "Invalid link",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input = "environment pc=3 registers=p:0,a:-2 queue=5,6 links=1\nenvironment pc=0 registers=p:1,a:0 queue= links=";
+        let snapshot = Snapshot::try_from(input).unwrap();
+        assert_eq!(snapshot.environments[0], EnvironmentSnapshot {
+            pc: 3,
+            registers: vec![(String::from("p"), BigInt::from(0)), (String::from("a"), BigInt::from(-2))],
+            queue: vec![5, 6],
+            links: vec![1]
+        });
+        assert_eq!(snapshot.to_string(), input);
+
+        // Restoring and capturing again gives back the same snapshot
+        let environments = snapshot.restore(&RegisterTable::new());
+        let borrowed = environments.iter().map(|environment| environment.borrow()).collect::<Vec<_>>();
+        let references = borrowed.iter().map(|environment| &**environment).collect::<Vec<&Environment>>();
+        assert_eq!(Snapshot::capture(&references), snapshot);
+
+        // Registers beyond an i64 are kept exactly
+        let input = "environment pc=0 registers=a:-123456789012345678901234567890 queue= links=";
+        let snapshot = Snapshot::try_from(input).unwrap();
+        assert_eq!(snapshot.restore(&RegisterTable::new())[0].borrow_mut().get(&"a"), i64::min_value());
+        assert_eq!(snapshot.to_string(), input);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Snapshot::try_from("environment pc=3"), Err(ParseError::InvalidFormat("environment pc=3")));
+        let input = "environment pc=x registers= queue= links=";
+        assert_eq!(Snapshot::try_from(input), Err(ParseError::NotANumber(input, "x")));
+        assert_eq!(Snapshot::try_from("environment pc=0 registers= queue= links=1"), Err(ParseError::InvalidLink(1)));
+    }
+}
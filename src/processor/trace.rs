@@ -0,0 +1,269 @@
+use super::environment::Environment;
+use super::arithmetic::BigInt;
+use super::instruction::{self, Instruction, InstructionType};
+use std::fmt::{self, Display, Formatter};
+use std::error::Error;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// A record of one executed instruction.
+///
+/// Records are written one per line as tab separated fields:
+/// `<program> <pc> <instruction> <effects>`, where effects is a space separated list of
+///  `<register>=<value>` for each changed register, `snd:<value>` for a sent value or played sound and
+///  `rcv:<value>` for a received value or recovered sound, or `-` if the instruction had no effects
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Record {
+    pub program: usize,
+    pub pc: i64,
+    pub instruction: String,
+    /// Every register the instruction changed and its exact value, in slot order. Optimised instructions may change several
+    pub changed: Vec<(String, BigInt)>,
+    pub sent: Option<i64>,
+    pub received: Option<i64>,
+}
+
+impl Record {
+    /// Creates the record of `instruction` having been executed at `pc`.
+    /// `before` holds the exact register values from before the instruction was executed, as given by `exact_registers`.
+    /// Returns None if the instruction didn't execute (i.e. a blocking `rcv`, or one that faulted and is rolled back)
+    pub fn new(program: usize, pc: i64, instruction: &Instruction, before: &[(String, BigInt)], environment: &Environment, result: Option<i64>) -> Option<Record> {
+        let instruction_type = instruction.get_type();
+        if (instruction_type == InstructionType::Rcv && result == None) || environment.has_fault() {
+            return None;
+        }
+        let registers = environment.get_register_table();
+        let changed = environment.exact_registers().into_iter().enumerate()
+            .filter(|&(slot, (_, ref value))| before.get(slot).map_or(!value.is_zero(), |&(_, ref previous)| previous != value))
+            .map(|(_, register)| register)
+            .collect();
+        let (sent, received) = match instruction_type {
+            InstructionType::Snd | InstructionType::Play => (result, None),
+            InstructionType::Rcv | InstructionType::Recover => (None, result),
+            _ => (None, None)
+        };
+        Some(Record { program, pc, instruction: instruction::disassemble(instruction, registers), changed, sent, received })
+    }
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut effects = Vec::new();
+        for &(ref register, ref value) in self.changed.iter() {
+            effects.push(format!("{}={}", register, value));
+        }
+        if let Some(value) = self.sent {
+            effects.push(format!("snd:{}", value));
+        }
+        if let Some(value) = self.received {
+            effects.push(format!("rcv:{}", value));
+        }
+        if effects.is_empty() {
+            effects.push(String::from("-"));
+        }
+        write!(f, "{}\t{}\t{}\t{}", self.program, self.pc, self.instruction, effects.join(" "))
+    }
+}
+
+impl <'a> TryFrom<&'a str> for Record {
+    type Error = ParseError<'a>;
+    fn try_from(input: &'a str) -> Result<Record, Self::Error> {
+        let parts = input.split("\t").collect::<Vec<&str>>();
+        if parts.len() != 4 {
+            return Err(ParseError::InvalidFormat(input));
+        }
+        let program = parts[0].parse::<usize>().map_err(|_| ParseError::NotANumber(input, parts[0]))?;
+        let pc = parts[1].parse::<i64>().map_err(|_| ParseError::NotANumber(input, parts[1]))?;
+        let mut record = Record { program, pc, instruction: String::from(parts[2]), changed: Vec::new(), sent: None, received: None };
+        for effect in parts[3].split(" ").filter(|&effect| effect != "-") {
+            let parse_value = |value: &'a str| value.parse::<i64>().map_err(|_| ParseError::NotANumber(input, value));
+            if effect.starts_with("snd:") {
+                record.sent = Some(parse_value(&effect[4..])?);
+            } else if effect.starts_with("rcv:") {
+                record.received = Some(parse_value(&effect[4..])?);
+            } else if let Some(i) = effect.find("=") {
+                let value = effect[i + 1..].parse::<BigInt>().map_err(|_| ParseError::NotANumber(input, &effect[i + 1..]))?;
+                record.changed.push((String::from(&effect[..i]), value));
+            } else {
+                return Err(ParseError::InvalidEffect(input, effect));
+            }
+        }
+        Ok(record)
+    }
+}
+
+/// Parses a trace of newline separated records, ignoring blank lines
+pub fn parse(input: &str) -> Result<Vec<Record>, ParseError> {
+    input.split("\n").filter(|line| !line.trim().is_empty()).map(Record::try_from).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    InvalidFormat(&'a str),
+    NotANumber(&'a str, &'a str),
+    InvalidEffect(&'a str, &'a str),
+}
+
+impl <'a> Display for ParseError<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidFormat(input) => write!(f, "Expected 4 tab separated fields in trace record: {}", input),
+            ParseError::NotANumber(input, nan) => write!(f, "Could not parse `{}` into a number in {}", nan, input),
+            ParseError::InvalidEffect(input, effect) => write!(f, "Unknown effect `{}` in {}", effect, input),
+        }
+    }
+}
+
+impl <'a> Error for ParseError<'a> {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::InvalidFormat(_) => "Invalid trace record",
+            ParseError::NotANumber(_, _) => "Not a number",
+            ParseError::InvalidEffect(_, _) => "Invalid effect",
+        }
+    }
+}
+
+/// Receives a record for every instruction a program executes
+pub trait Tracer {
+    fn trace(&mut self, record: Record);
+}
+
+/// A tracer that writes records to `output`, one per line
+pub struct TraceWriter<W: Write> {
+    output: W,
+    error: Option<io::Error>,
+}
+
+impl <W: Write> TraceWriter<W> {
+    pub fn new(output: W) -> TraceWriter<W> {
+        TraceWriter { output, error: None }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Flushes the output, returning the first error encountered while tracing
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.output.flush()
+    }
+}
+
+impl <W: Write> Tracer for TraceWriter<W> {
+    fn trace(&mut self, record: Record) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.output, "{}", record) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// The first point at which a re-execution diverged from a recorded trace.
+/// `line` is the 1-indexed record in the trace, and either side is None if the trace or execution ended early
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub expected: Option<Record>,
+    pub actual: Option<Record>,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let describe = |record: &Option<Record>| match *record {
+            Some(ref record) => format!("`{}`", record),
+            None => String::from("end of trace")
+        };
+        write!(f, "Trace diverged at record {}: expected {}, got {}", self.line, describe(&self.expected), describe(&self.actual))
+    }
+}
+
+/// A tracer that replays a recorded trace, checking each executed instruction against it
+pub struct TraceChecker {
+    expected: Vec<Record>,
+    position: usize,
+    mismatch: Option<Mismatch>,
+}
+
+impl TraceChecker {
+    pub fn new(expected: Vec<Record>) -> TraceChecker {
+        TraceChecker { expected, position: 0, mismatch: None }
+    }
+
+    /// Gets the number of matching records, or the first mismatch
+    pub fn result(&self) -> Result<usize, &Mismatch> {
+        if let Some(ref mismatch) = self.mismatch {
+            return Err(mismatch);
+        }
+        Ok(self.position)
+    }
+
+    /// Checks that the whole trace was replayed.
+    /// Returns the number of records replayed, or the first mismatch
+    pub fn finish(&mut self) -> Result<usize, Mismatch> {
+        if self.mismatch.is_none() && self.position < self.expected.len() {
+            self.mismatch = Some(Mismatch { line: self.position + 1, expected: Some(self.expected[self.position].clone()), actual: None });
+        }
+        match self.mismatch.take() {
+            Some(mismatch) => Err(mismatch),
+            None => Ok(self.position)
+        }
+    }
+}
+
+impl Tracer for TraceChecker {
+    fn trace(&mut self, record: Record) {
+        if self.mismatch.is_some() {
+            return;
+        }
+        if self.expected.get(self.position) == Some(&record) {
+            self.position += 1;
+            return;
+        }
+        self.mismatch = Some(Mismatch { line: self.position + 1, expected: self.expected.get(self.position).cloned(), actual: Some(record) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_round_trip() {
+        let inputs = ["0\t3\tset a 5\ta=5", "1\t0\tsnd p\tsnd:1", "0\t7\trcv a\ta=-2 rcv:-2", "0\t2\tjnz 1 2\t-", "0\t4\tmul a 0\ta=0 b=6", "0\t1\tmul a 2\ta=18446744073709551614"];
+        for input in inputs.iter() {
+            let record = Record::try_from(*input).unwrap();
+            assert_eq!(&record.to_string(), input);
+        }
+        let record = Record::try_from("0\t7\trcv a\ta=-2 rcv:-2").unwrap();
+        assert_eq!(record.changed, vec![(String::from("a"), BigInt::from(-2))]);
+        assert_eq!(record.received, Some(-2));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Record::try_from("0\t3\tset a 5"), Err(ParseError::InvalidFormat("0\t3\tset a 5")));
+        assert_eq!(Record::try_from("0\tx\tset a 5\ta=5"), Err(ParseError::NotANumber("0\tx\tset a 5\ta=5", "x")));
+        assert_eq!(Record::try_from("0\t3\tset a 5\ta:5"), Err(ParseError::InvalidEffect("0\t3\tset a 5\ta:5", "a:5")));
+    }
+
+    #[test]
+    fn test_checker() {
+        let expected = parse("0\t0\tset a 1\ta=1\n0\t1\tset a 2\ta=2\n").unwrap();
+        let mut checker = TraceChecker::new(expected.clone());
+        checker.trace(expected[0].clone());
+        assert_eq!(checker.result(), Ok(1));
+        assert_eq!(checker.finish(), Err(Mismatch { line: 2, expected: Some(expected[1].clone()), actual: None }));
+
+        let mut checker = TraceChecker::new(expected.clone());
+        let mut diverged = expected[1].clone();
+        diverged.changed = vec![(String::from("a"), BigInt::from(3))];
+        checker.trace(expected[0].clone());
+        checker.trace(diverged.clone());
+        assert_eq!(checker.finish(), Err(Mismatch { line: 2, expected: Some(expected[1].clone()), actual: Some(diverged) }));
+    }
+}
@@ -1,16 +1,27 @@
-#[derive(Debug, PartialEq, Eq)]
+use super::register_table::RegisterTable;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 /// Represents a literal or register value
+/// Registers are referred to by their slot in a `RegisterTable`
 pub enum Value {
     Literal(i64),
-    Register(String),
+    Register(usize),
 }
 
 impl Value {
-    pub fn parse(input: &str) -> Value {
+    pub fn parse(input: &str, registers: &mut RegisterTable) -> Value {
         let parsed = input.parse::<i64>().ok();
         match parsed {
             Some(value) => Value::Literal(value),
-            None => Value::Register(input.into())
+            None => Value::Register(registers.slot(input))
+        }
+    }
+
+    /// Formats the value as it would appear in a program, using `registers` to name register slots
+    pub fn format(&self, registers: &RegisterTable) -> String {
+        match *self {
+            Value::Literal(value) => value.to_string(),
+            Value::Register(slot) => registers.name(slot).map(String::from).unwrap_or_else(|| format!("r{}", slot))
         }
     }
 }
@@ -22,22 +33,38 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let mut registers = RegisterTable::new();
         let mut input = "a";
-        assert_eq!(Value::parse(input), Value::Register("a".into()));
+        assert_eq!(Value::parse(input, &mut registers), Value::Register(0));
 
         input = "10";
-        assert_eq!(Value::parse(input), Value::Literal(10));
+        assert_eq!(Value::parse(input, &mut registers), Value::Literal(10));
+
+        input = "b";
+        assert_eq!(Value::parse(input, &mut registers), Value::Register(1));
+        assert_eq!(registers.name(1), Some("b"));
+    }
+
+    #[test]
+    fn test_format() {
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        assert_eq!(Value::Register(a).format(&registers), "a");
+        assert_eq!(Value::Literal(-3).format(&registers), "-3");
     }
 
     #[test]
     fn test_get_value() {
-        let mut environment = Environment::new();
+        let mut registers = RegisterTable::new();
+        let a = registers.slot("a");
+        let b = registers.slot("b");
+        let mut environment = Environment::with_registers(registers);
 
         assert_eq!(environment.get_value(&Value::Literal(10)), 10);
 
         environment.set(&"a", 5);
-        assert_eq!(environment.get_value(&Value::Register("a".into())), 5);
-        assert_eq!(environment.get_value(&Value::Register("b".into())), 0);
+        assert_eq!(environment.get_value(&Value::Register(a)), 5);
+        assert_eq!(environment.get_value(&Value::Register(b)), 0);
     }
 
 }
\ No newline at end of file
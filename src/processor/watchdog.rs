@@ -0,0 +1,131 @@
+use super::arithmetic::ArithmeticError;
+use super::device::DeviceError;
+use super::cycle::Cycle;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// How long an execution may run before it is interrupted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    /// No limits, execution runs until the program stops by itself
+    pub fn none() -> Limits {
+        Limits { max_steps: None, timeout: None }
+    }
+
+    /// Limits execution to `max_steps` steps
+    pub fn steps(max_steps: u64) -> Limits {
+        Limits { max_steps: Some(max_steps), timeout: None }
+    }
+}
+
+/// Why an execution was stopped before its program finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The execution was about to exceed the maximum number of steps
+    StepLimit(u64),
+    /// The execution ran for longer than the timeout
+    TimeLimit(Duration),
+    /// The instruction at `pc` failed to calculate its result
+    Arithmetic { pc: i64, error: ArithmeticError },
+    /// The instruction at `pc` failed to send or receive through a device
+    Device { pc: i64, error: DeviceError },
+    /// The machine came back to a state it had already been in, so it would never stop
+    InfiniteLoop(Cycle),
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Fault::StepLimit(steps) => write!(f, "step limit of {} reached", steps),
+            Fault::TimeLimit(timeout) => write!(f, "time limit of {}.{:03}s reached", timeout.as_secs(), timeout.subsec_nanos() / 1_000_000),
+            Fault::Arithmetic { pc, error } => write!(f, "{} at pc {}", error, pc),
+            Fault::Device { pc, error } => write!(f, "{} at pc {}", error, pc),
+            Fault::InfiniteLoop(cycle) => write!(f, "{}", cycle),
+        }
+    }
+}
+
+/// A fault, along with the state of the machine when it occurred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interrupted<S> {
+    pub fault: Fault,
+    pub state: S,
+}
+
+impl <S: Display> Display for Interrupted<S> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "interrupted: {}\n{}", self.fault, self.state)
+    }
+}
+
+/// The number of steps between checks of the clock, reading it on every step is comparatively slow
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Counts the steps of an execution and faults once it exceeds its limits
+pub struct Watchdog {
+    limits: Limits,
+    steps: u64,
+    start: Instant,
+}
+
+impl Watchdog {
+    pub fn new(limits: Limits) -> Watchdog {
+        Watchdog { limits, steps: 0, start: Instant::now() }
+    }
+
+    /// Records that a step is about to be taken.
+    /// Faults if taking the step would break a limit
+    pub fn tick(&mut self) -> Result<(), Fault> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps >= max_steps {
+                return Err(Fault::StepLimit(max_steps));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.steps % CLOCK_INTERVAL == 0 && self.start.elapsed() > timeout {
+                return Err(Fault::TimeLimit(timeout));
+            }
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// The number of steps taken so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_step_limit() {
+        let mut watchdog = Watchdog::new(Limits::steps(2));
+        assert_eq!(watchdog.tick(), Ok(()));
+        assert_eq!(watchdog.tick(), Ok(()));
+        assert_eq!(watchdog.tick(), Err(Fault::StepLimit(2)));
+        assert_eq!(watchdog.steps(), 2);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let mut watchdog = Watchdog::new(Limits { max_steps: None, timeout: Some(Duration::from_secs(0)) });
+        ::std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(watchdog.tick(), Err(Fault::TimeLimit(Duration::from_secs(0))));
+    }
+
+    #[test]
+    fn test_no_limits() {
+        let mut watchdog = Watchdog::new(Limits::none());
+        for _ in 0..10_000 {
+            assert_eq!(watchdog.tick(), Ok(()));
+        }
+    }
+}